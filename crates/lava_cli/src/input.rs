@@ -0,0 +1,200 @@
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use lava_core::{Idl, LavaConfig, LavaConfigJSON};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// The syntax a workspace file is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// Picks the format from the file extension, falling back to sniffing the
+    /// content: JSON workspaces are always a single object.
+    pub fn detect(path: &Path, content: &str) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Format::Json,
+            Some("yaml") | Some("yml") => Format::Yaml,
+            _ if content.trim_start().starts_with('{') => Format::Json,
+            _ => Format::Yaml,
+        }
+    }
+}
+
+/// The layout of a workspace, independent of its syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shape {
+    /// `LavaConfig`'s own layout, with `wallets`, `mints`, `atas`, `programs`
+    /// and `pdas` maps.
+    Config,
+    /// The layout the web app exports, with one `accounts` array tagged by `kind`.
+    Export,
+}
+
+impl Shape {
+    pub fn detect(workspace: &Value) -> Shape {
+        if workspace.get("accounts").is_some_and(|a| a.is_array()) {
+            Shape::Export
+        } else {
+            Shape::Config
+        }
+    }
+}
+
+/// A workspace in either [`Shape`].
+pub enum Workspace {
+    Config(Box<LavaConfig>),
+    Export(LavaConfigJSON),
+}
+
+impl Workspace {
+    pub fn into_config(self) -> Result<LavaConfig> {
+        match self {
+            Workspace::Config(config) => Ok(*config),
+            Workspace::Export(json) => LavaConfig::try_from(&json),
+        }
+    }
+
+    pub fn into_export(self) -> LavaConfigJSON {
+        match self {
+            Workspace::Config(config) => LavaConfigJSON::from(config.as_ref()),
+            Workspace::Export(json) => json,
+        }
+    }
+}
+
+/// Reads a workspace file in either syntax and either shape, reporting parse
+/// errors with the line, column and key they were found at.
+pub fn read_workspace(path: &str) -> Result<Workspace> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| Error::msg(format!("Could not read {}: {}", path, e)))?;
+    let workspace: Value = parse_workspace(path, &content)?;
+    // Parse again into the detected shape so errors still carry a location.
+    match Shape::detect(&workspace) {
+        Shape::Config => Ok(Workspace::Config(Box::new(parse_workspace(
+            path, &content,
+        )?))),
+        Shape::Export => Ok(Workspace::Export(parse_workspace(path, &content)?)),
+    }
+}
+
+/// Reads an Anchor IDL JSON file, of either IDL format.
+pub fn read_idl(path: &str) -> Result<Idl> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| Error::msg(format!("Could not read {}: {}", path, e)))?;
+    parse_workspace(path, &content)
+}
+
+/// Writes `workspace` as YAML or JSON depending on the extension of `path`.
+pub fn write_workspace<T: Serialize>(path: &str, workspace: &T) -> Result<()> {
+    let content = match Format::detect(Path::new(path), "{") {
+        Format::Json => serde_json::to_string_pretty(workspace)?,
+        Format::Yaml => serde_yaml::to_string(workspace)?,
+    };
+    fs::write(path, content).map_err(|e| Error::msg(format!("Could not write {}: {}", path, e)))
+}
+
+pub fn parse_workspace<T: DeserializeOwned>(path: &str, content: &str) -> Result<T> {
+    match Format::detect(Path::new(path), content) {
+        Format::Json => {
+            let de = &mut serde_json::Deserializer::from_str(content);
+            serde_path_to_error::deserialize(de).map_err(|e| {
+                let location = format!("{}:{}:{}", path, e.inner().line(), e.inner().column());
+                located(location, e.path(), e.inner())
+            })
+        }
+        Format::Yaml => {
+            let de = serde_yaml::Deserializer::from_str(content);
+            serde_path_to_error::deserialize(de).map_err(|e| {
+                let location = match e.inner().location() {
+                    Some(l) => format!("{}:{}:{}", path, l.line(), l.column()),
+                    None => path.to_string(),
+                };
+                located(location, e.path(), e.inner())
+            })
+        }
+    }
+}
+
+fn located(location: String, key: &serde_path_to_error::Path, e: &impl std::fmt::Display) -> Error {
+    // Both parsers append their own "at line X column Y"; we lead with it instead.
+    let message = e.to_string();
+    let message = message.split(" at line ").next().unwrap_or_default();
+    if key.to_string() == "." {
+        Error::msg(format!("{}: {}", location, message))
+    } else {
+        Error::msg(format!("{}: at `{}`: {}", location, key, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug)]
+    struct Wallet {
+        #[allow(dead_code)]
+        balance: u64,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Wallets {
+        #[allow(dead_code)]
+        wallets: Vec<Wallet>,
+    }
+
+    #[test]
+    fn detects_format() {
+        assert_eq!(Format::detect(Path::new("lava.yml"), "{}"), Format::Yaml);
+        assert_eq!(
+            Format::detect(Path::new("lava.json"), "name: x"),
+            Format::Json
+        );
+        assert_eq!(
+            Format::detect(Path::new("lava"), "  {\"a\": 1}"),
+            Format::Json
+        );
+        assert_eq!(
+            Format::detect(Path::new("lava"), "# comment\na: 1"),
+            Format::Yaml
+        );
+    }
+
+    #[test]
+    fn reads_both_shapes() {
+        let example = "../../workspaces_examples/Escrow2024-Make-and-Take.json";
+        let export = read_workspace(example).unwrap();
+        assert!(matches!(export, Workspace::Export(_)));
+        let config = export.into_config().unwrap();
+
+        let path = std::env::temp_dir().join("lava-reads-both-shapes.yaml");
+        let path = path.to_str().unwrap();
+        write_workspace(path, &config).unwrap();
+        let reread = read_workspace(path).unwrap();
+        assert!(matches!(reread, Workspace::Config(_)));
+        let round_trip = LavaConfig::try_from(&reread.into_export()).unwrap();
+        assert_eq!(round_trip.to_mocha().unwrap(), config.to_mocha().unwrap());
+    }
+
+    #[test]
+    fn points_at_the_offending_key() {
+        let yaml = "# maker funds\nwallets:\n  - balance: 10\n  - balance: lots\n";
+        let err = parse_workspace::<Wallets>("lava.yaml", yaml).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("lava.yaml:4:14: at `wallets[1].balance`"));
+
+        let json = "{\n  \"wallets\": [{ \"balance\": \"lots\" }]\n}";
+        let err = parse_workspace::<Wallets>("lava.json", json).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("lava.json:2:33: at `wallets[0].balance`: invalid type"));
+    }
+}
@@ -1,41 +1,158 @@
-// use clap::{Arg};
-// use serde::{Deserialize, Serialize};
-use clap::Parser;
-use lava_core::LavaConfig;
+use clap::{Parser, Subcommand, ValueEnum};
+use input::Shape;
+use lava_core::codegen::{Bankrun, Jest, Mocha, Vitest};
+use lava_core::LavaConfigJSON;
 use std::fs::File;
 use std::io::Write;
+use std::process::ExitCode;
+
+mod input;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(name = "lava", author, version, about, long_about = None)]
 struct Args {
-    /// Path of lava.yaml file
-    #[arg(short, long)]
-    src: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate tests from a workspace
+    Generate {
+        /// Path of lava.yaml or lava.json file
+        #[arg(short, long)]
+        src: String,
+
+        /// Output path for the generated tests
+        #[arg(short, long)]
+        out: String,
+
+        /// Kind of tests to generate
+        #[arg(short, long, value_enum, default_value_t = Target::Mocha)]
+        target: Target,
+    },
+    /// Check a workspace for problems without generating anything
+    Validate {
+        /// Path of lava.yaml or lava.json file
+        #[arg(short, long)]
+        src: String,
+    },
+    /// Scaffold a starter workspace from Anchor IDL files
+    Init {
+        /// Paths of Anchor IDL JSON files, e.g. target/idl/*.json
+        #[arg(long, required = true, num_args = 1..)]
+        idl: Vec<String>,
+
+        /// Output path for the workspace; .yaml/.yml writes YAML
+        #[arg(short, long, default_value = "lava.yaml")]
+        out: String,
 
-    /// Output directory for mocha test
-    #[arg(short, long)]
-    out: String,
+        /// Name of the workspace, used for the top-level describe block
+        #[arg(short, long, default_value = "Lava")]
+        name: String,
+    },
+    /// Convert a workspace between shapes, and between YAML and JSON
+    Convert {
+        /// Path of lava.yaml or lava.json file
+        #[arg(short, long)]
+        src: String,
+
+        /// Output path for the workspace; .yaml/.yml writes YAML
+        #[arg(short, long)]
+        out: String,
+
+        /// Shape to write the workspace in
+        #[arg(long, value_enum, default_value_t = Shape::Export)]
+        to: Shape,
+    },
+}
+
+/// Kinds of tests `lava generate` can write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Target {
+    /// TypeScript mocha tests run by `anchor test`
+    Mocha,
+    /// TypeScript jest tests
+    Jest,
+    /// TypeScript vitest tests
+    Vitest,
+    /// TypeScript mocha tests run against solana-bankrun, without a validator
+    Bankrun,
+    /// Rust tests run by `cargo test` with solana-program-test
+    Rust,
 }
 
-fn main() {
+fn main() -> ExitCode {
     let args = Args::parse();
 
-    match read_and_parse_json(&args.src, &args.out) {
-        Ok(_) => {
-            println!("✅ Successfully built Mocha test!");
+    let result = match &args.command {
+        Command::Generate { src, out, target } => generate(src, out, *target)
+            .map(|_| println!("✅ Successfully built {:?} test!", target)),
+        Command::Validate { src } => validate(src).map(|_| println!("✅ {} has no problems!", src)),
+        Command::Init { idl, out, name } => {
+            init(idl, out, name).map(|_| println!("✅ Successfully wrote {}!", out))
+        }
+        Command::Convert { src, out, to } => {
+            convert(src, out, *to).map(|_| println!("✅ Successfully converted workspace!"))
+        }
+    };
+
+    match result {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Oops, looks like something went wrong: {}", e);
+            ExitCode::FAILURE
         }
-        Err(e) => eprintln!("Oops, looks like something went wrong: {}", e),
     }
 }
 
-fn read_and_parse_json(file_path: &str, out_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // Open and read the YAML file
-    let file = File::open(file_path)?;
-
-    // Parse JSON into your Config struct
-    let config: LavaConfig = serde_json::from_reader(file)?;
-    println!("{:?}", config);
+fn generate(
+    file_path: &str,
+    out_path: &str,
+    target: Target,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Parse YAML or JSON, in either shape, into your Config struct
+    let config = input::read_workspace(file_path)?.into_config()?;
+    let tests = match target {
+        Target::Mocha => config.try_generate(&Mocha)?,
+        Target::Jest => config.try_generate(&Jest)?,
+        Target::Vitest => config.try_generate(&Vitest)?,
+        Target::Bankrun => config.try_generate(&Bankrun)?,
+        Target::Rust => config.try_to_rust_tests()?,
+    };
     let mut file = File::create(out_path)?;
-    file.write_all(config.to_mocha().as_bytes())?;
+    file.write_all(tests.as_bytes())?;
+    Ok(())
+}
+
+fn validate(file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let config = input::read_workspace(file_path)?.into_config()?;
+    config.validate()?;
+    Ok(())
+}
+
+fn init(
+    idl_paths: &[String],
+    out_path: &str,
+    name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let idls = idl_paths
+        .iter()
+        .map(|path| input::read_idl(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    input::write_workspace(out_path, &LavaConfigJSON::from_idls(name, idls))?;
+    Ok(())
+}
+
+fn convert(
+    file_path: &str,
+    out_path: &str,
+    shape: Shape,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let workspace = input::read_workspace(file_path)?;
+    match shape {
+        Shape::Config => input::write_workspace(out_path, &workspace.into_config()?)?,
+        Shape::Export => input::write_workspace(out_path, &workspace.into_export())?,
+    }
     Ok(())
 }
@@ -0,0 +1,351 @@
+use convert_case::{Case, Casing};
+use serde_json::Value;
+use soda_sol::structs::InstructionType;
+
+use crate::codegen::ArgValue;
+use crate::idl::Idl;
+use crate::lava_config::LavaConfig;
+
+/// A type from the IDL `types` (or `accounts`) section that args can reference
+/// through `defined`.
+pub(crate) enum DefinedType {
+    Struct(Vec<(String, InstructionType)>),
+    Enum(Vec<String>),
+}
+
+/// `soda_sol` keeps the layout of user-defined types private, so it is read
+/// out of the IDL's normalized JSON.
+pub(crate) fn defined_type(idl: &Idl, name: &str) -> Result<DefinedType, String> {
+    let idl = idl.json();
+    let defined = ["types", "accounts"]
+        .iter()
+        .filter_map(|section| idl[section].as_array())
+        .flatten()
+        .find(|t| t["name"] == name)
+        .ok_or(format!("type \"{}\" is not defined in the IDL", name))?;
+    let ty = &defined["type"];
+    match ty["kind"].as_str() {
+        Some("enum") => Ok(DefinedType::Enum(
+            ty["variants"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v["name"].as_str().map(|n| n.to_string()))
+                .collect(),
+        )),
+        _ => ty["fields"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|f| {
+                let kind: InstructionType =
+                    serde_json::from_value(f["type"].clone()).map_err(|e| e.to_string())?;
+                Ok((f["name"].as_str().unwrap_or_default().to_string(), kind))
+            })
+            .collect::<Result<Vec<_>, String>>()
+            .map(DefinedType::Struct),
+    }
+}
+
+/// Parses an integer of the given width, given as a JSON number or, for
+/// widths JSON can't hold, a string. Returns it in decimal.
+pub(crate) fn integer(value: &Value, signed: bool, bits: u32) -> Result<String, String> {
+    let text = match value {
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.replace('_', ""),
+        _ => return Err(format!("expected an integer, got {}", value)),
+    };
+    let name = format!("{}{}", if signed { "i" } else { "u" }, bits);
+    let n = if signed {
+        text.parse::<i128>()
+            .ok()
+            .filter(|n| bits == 128 || (-(1i128 << (bits - 1))..(1i128 << (bits - 1))).contains(n))
+            .map(|n| n.to_string())
+    } else {
+        text.parse::<u128>()
+            .ok()
+            .filter(|n| bits == 128 || *n < (1u128 << bits))
+            .map(|n| n.to_string())
+    };
+    n.ok_or(format!("\"{}\" is not a valid {}", text, name))
+}
+
+pub(crate) fn elements(value: &Value) -> Result<&Vec<Value>, String> {
+    value
+        .as_array()
+        .ok_or(format!("expected an array, got {}", value))
+}
+
+pub(crate) fn hex_bytes(hex: &str) -> Result<Vec<u8>, String> {
+    let digits = hex.trim_start_matches("0x");
+    if !digits.is_ascii() || !digits.len().is_multiple_of(2) {
+        return Err(format!("\"{}\" is not hex encoded bytes", hex));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("\"{}\" is not hex encoded bytes", hex))
+        })
+        .collect()
+}
+
+impl LavaConfig {
+    /// Checks a test argument against its IDL type `kind` and shapes it into
+    /// an [`ArgValue`] backends can render.
+    pub(crate) fn arg_value(
+        &self,
+        idl: &Idl,
+        kind: &InstructionType,
+        value: &Value,
+    ) -> Result<ArgValue, String> {
+        let int = |signed, bits| {
+            integer(value, signed, bits).map(|value| ArgValue::Integer {
+                value,
+                signed,
+                bits,
+            })
+        };
+        match kind {
+            InstructionType::U8 => int(false, 8),
+            InstructionType::U16 => int(false, 16),
+            InstructionType::U32 => int(false, 32),
+            InstructionType::U64 => int(false, 64),
+            InstructionType::U128 => int(false, 128),
+            InstructionType::I8 => int(true, 8),
+            InstructionType::I16 => int(true, 16),
+            InstructionType::I32 => int(true, 32),
+            InstructionType::I64 => int(true, 64),
+            InstructionType::I128 => int(true, 128),
+            InstructionType::Bool => match value {
+                Value::Bool(b) => Ok(ArgValue::Bool(*b)),
+                Value::String(s) if s == "true" || s == "false" => Ok(ArgValue::Bool(s == "true")),
+                _ => Err(format!("expected a bool, got {}", value)),
+            },
+            InstructionType::String => match value {
+                Value::String(s) => Ok(ArgValue::String(s.clone())),
+                _ => Err(format!("expected a string, got {}", value)),
+            },
+            InstructionType::PublicKey => {
+                let key = value.as_str().ok_or(format!(
+                    "expected an account name or address, got {}",
+                    value
+                ))?;
+                Ok(ArgValue::PublicKey(self.resolve(key)))
+            }
+            InstructionType::Bytes => match value {
+                Value::String(hex) => hex_bytes(hex).map(ArgValue::Bytes),
+                Value::Array(items) => items
+                    .iter()
+                    .map(|b| integer(b, false, 8).map(|b| b.parse::<u8>().unwrap_or_default()))
+                    .collect::<Result<Vec<u8>, String>>()
+                    .map(ArgValue::Bytes),
+                _ => Err(format!("expected hex bytes or an array, got {}", value)),
+            },
+            InstructionType::Option(inner) => Ok(ArgValue::Option {
+                kind: *inner.clone(),
+                value: match value {
+                    Value::Null => None,
+                    _ => Some(Box::new(self.arg_value(idl, inner, value)?)),
+                },
+            }),
+            InstructionType::Array(inner, len) => {
+                let items = elements(value)?;
+                if items.len() != *len {
+                    return Err(format!("expected {} elements, got {}", len, items.len()));
+                }
+                Ok(ArgValue::Array {
+                    kind: *inner.clone(),
+                    items: self.list_value(idl, inner, items)?,
+                })
+            }
+            InstructionType::Vec(inner) => Ok(ArgValue::Vec {
+                kind: *inner.clone(),
+                items: self.list_value(idl, inner, elements(value)?)?,
+            }),
+            InstructionType::HashSet(inner) | InstructionType::BTreeSet(inner) => {
+                Ok(ArgValue::Set {
+                    kind: *inner.clone(),
+                    items: self.list_value(idl, inner, elements(value)?)?,
+                    ordered: matches!(kind, InstructionType::BTreeSet(_)),
+                })
+            }
+            InstructionType::Tuple(kinds) => {
+                let items = elements(value)?;
+                if items.len() != kinds.len() {
+                    return Err(format!(
+                        "expected {} elements, got {}",
+                        kinds.len(),
+                        items.len()
+                    ));
+                }
+                kinds
+                    .iter()
+                    .zip(items)
+                    .map(|(k, v)| self.arg_value(idl, k, v))
+                    .collect::<Result<Vec<ArgValue>, String>>()
+                    .map(ArgValue::Tuple)
+            }
+            InstructionType::HashMap(key, val) | InstructionType::BTreeMap(key, val) => {
+                let entries = match value {
+                    Value::Object(o) => o
+                        .iter()
+                        .map(|(k, v)| (Value::String(k.clone()), v.clone()))
+                        .collect::<Vec<_>>(),
+                    _ => elements(value)?
+                        .iter()
+                        .map(|pair| match pair.as_array().map(|p| p.as_slice()) {
+                            Some([k, v]) => Ok((k.clone(), v.clone())),
+                            _ => Err(format!("expected a [key, value] pair, got {}", pair)),
+                        })
+                        .collect::<Result<Vec<_>, String>>()?,
+                };
+                Ok(ArgValue::Map {
+                    key: *key.clone(),
+                    value: *val.clone(),
+                    entries: entries
+                        .iter()
+                        .map(|(k, v)| {
+                            Ok((self.arg_value(idl, key, k)?, self.arg_value(idl, val, v)?))
+                        })
+                        .collect::<Result<Vec<_>, String>>()?,
+                    ordered: matches!(kind, InstructionType::BTreeMap(_, _)),
+                })
+            }
+            InstructionType::Defined(name) => match defined_type(idl, name)? {
+                DefinedType::Struct(fields) => {
+                    let object = value
+                        .as_object()
+                        .ok_or(format!("expected a {} object, got {}", name, value))?;
+                    let fields = fields
+                        .iter()
+                        .map(|(field, kind)| {
+                            let v = object
+                                .iter()
+                                .find(|(k, _)| k.to_case(Case::Camel) == field.to_case(Case::Camel))
+                                .map(|(_, v)| v)
+                                .ok_or(format!(
+                                    "{} is missing field \"{}\"",
+                                    name,
+                                    field.to_case(Case::Camel)
+                                ))?;
+                            Ok((field.clone(), self.arg_value(idl, kind, v)?))
+                        })
+                        .collect::<Result<Vec<_>, String>>()?;
+                    Ok(ArgValue::Struct {
+                        name: name.clone(),
+                        fields,
+                    })
+                }
+                DefinedType::Enum(variants) => {
+                    let variant = value
+                        .as_str()
+                        .ok_or(format!("expected a {} variant name, got {}", name, value))?;
+                    let variant = variants
+                        .iter()
+                        .find(|v| v.to_case(Case::Camel) == variant.to_case(Case::Camel))
+                        .ok_or(format!("\"{}\" is not a variant of {}", variant, name))?;
+                    Ok(ArgValue::Enum {
+                        name: name.clone(),
+                        variant: variant.clone(),
+                    })
+                }
+            },
+        }
+    }
+
+    fn list_value(
+        &self,
+        idl: &Idl,
+        kind: &InstructionType,
+        items: &[Value],
+    ) -> Result<Vec<ArgValue>, String> {
+        items.iter().map(|v| self.arg_value(idl, kind, v)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use crate::idl::Idl;
+    use serde_json::{json, Value};
+    use soda_sol::structs::InstructionType;
+
+    use crate::LavaConfig;
+
+    fn dice() -> (LavaConfig, Idl) {
+        let config = fixtures::config(&fixtures::dice());
+        let mut idl: Value = serde_json::to_value(&config.idls[0]).unwrap();
+        idl["types"] = json!([
+            { "name": "BetKind", "type": { "kind": "enum", "variants": [{ "name": "High" }, { "name": "Low" }] } },
+            { "name": "Range", "type": { "kind": "struct", "fields": [
+                { "name": "min_roll", "type": "u8" },
+                { "name": "kind", "type": { "defined": "BetKind" } }
+            ] } }
+        ]);
+        (config, serde_json::from_value(idl).unwrap())
+    }
+
+    fn encode(kind: Value, value: Value) -> Result<String, String> {
+        let (config, idl) = dice();
+        let kind: InstructionType = serde_json::from_value(kind).unwrap();
+        config
+            .arg_value(&idl, &kind, &value)
+            .map(|v| crate::codegen::arg(&v))
+    }
+
+    #[test]
+    fn encodes_integers() {
+        assert_eq!(encode(json!("u8"), json!("7")).unwrap(), "7");
+        assert_eq!(encode(json!("i16"), json!(-3)).unwrap(), "-3");
+        assert_eq!(
+            encode(json!("u64"), json!("1000000")).unwrap(),
+            "new BN(1000000)"
+        );
+        assert_eq!(
+            encode(
+                json!("u128"),
+                json!("340282366920938463463374607431768211455")
+            )
+            .unwrap(),
+            r#"new BN("340282366920938463463374607431768211455")"#
+        );
+        assert_eq!(
+            encode(json!("i128"), json!("-9007199254740993")).unwrap(),
+            r#"new BN("-9007199254740993")"#
+        );
+        assert!(encode(json!("u8"), json!(256)).is_err());
+        assert!(encode(json!("i8"), json!("-129")).is_err());
+    }
+
+    #[test]
+    fn encodes_compound_types() {
+        assert_eq!(encode(json!("bool"), json!(true)).unwrap(), "true");
+        assert_eq!(encode(json!("string"), json!("hi")).unwrap(), r#""hi""#);
+        assert_eq!(
+            encode(json!("publicKey"), json!("house")).unwrap(),
+            r#"accountsPublicKeys["house"]"#
+        );
+        assert_eq!(
+            encode(json!("bytes"), json!("0xdead")).unwrap(),
+            r#"Buffer.from("dead", "hex")"#
+        );
+        assert_eq!(
+            encode(json!({ "vec": "u64" }), json!([1, "2"])).unwrap(),
+            "[new BN(1), new BN(2)]"
+        );
+        assert!(encode(json!({ "array": ["u8", 2] }), json!([1])).is_err());
+        assert_eq!(
+            encode(json!({ "option": "u8" }), Value::Null).unwrap(),
+            "null"
+        );
+        assert_eq!(
+            encode(
+                json!({ "defined": "Range" }),
+                json!({ "minRoll": 50, "kind": "High" })
+            )
+            .unwrap(),
+            "{ minRoll: 50, kind: { high: {} } }"
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use soda_sol::structs::InstructionType;
+
+use crate::args::{defined_type, DefinedType};
+use crate::codegen::{AccountRef, Assertion, Delta};
+use crate::error::LavaErrorKind;
+use crate::idl::Idl;
+use crate::lava_config::LavaConfig;
+
+/// A check on account state after a test's call.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum LavaAssert {
+    /// The account holds exactly `lamports`.
+    Balance { account: String, lamports: u64 },
+    /// The token account holds exactly `amount`, in base units.
+    TokenBalance { account: String, amount: u64 },
+    /// The account exists.
+    Exists { account: String },
+    /// The account doesn't exist, e.g. because the call closed it.
+    Closed { account: String },
+    /// The account decodes as the program account `type` of the called
+    /// program, with the listed `fields`. Fields left out aren't checked.
+    Data {
+        account: String,
+        #[serde(rename = "type")]
+        account_type: String,
+        fields: IndexMap<String, Value>,
+    },
+}
+
+impl LavaAssert {
+    fn account(&self) -> &str {
+        match self {
+            LavaAssert::Balance { account, .. }
+            | LavaAssert::TokenBalance { account, .. }
+            | LavaAssert::Exists { account }
+            | LavaAssert::Closed { account }
+            | LavaAssert::Data { account, .. } => account,
+        }
+    }
+}
+
+/// How much an account's balance may change over a test's call: exactly
+/// `change`, `change` less up to `fees` lamports, or anywhere between `min`
+/// and `max`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LavaDelta {
+    pub(crate) account: String,
+    #[serde(default)]
+    pub(crate) kind: DeltaKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) change: Option<i128>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) fees: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) min: Option<i128>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) max: Option<i128>,
+}
+
+/// The balance a [`LavaDelta`] tracks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeltaKind {
+    /// SOL, in lamports.
+    #[default]
+    Lamports,
+    /// Tokens held by a token account, in base units.
+    Tokens,
+}
+
+impl LavaConfig {
+    /// Resolves the account a check reads, which must be one the test
+    /// declares an address for.
+    pub(crate) fn checked_account(&self, name: &str) -> Result<AccountRef, String> {
+        match self.resolve(name) {
+            account @ (AccountRef::Keypair(_) | AccountRef::Address(_)) => Ok(account),
+            _ => Err(format!(
+                "\"{}\" is not a declared wallet, mint, PDA or ATA",
+                name
+            )),
+        }
+    }
+
+    /// Checks `assert` against the workspace and the IDL of the program the
+    /// test calls, and shapes it into an [`Assertion`].
+    pub(crate) fn assertion(
+        &self,
+        idl: &Idl,
+        assert: &LavaAssert,
+    ) -> Result<Assertion, (LavaErrorKind, String)> {
+        let account = self
+            .checked_account(assert.account())
+            .map_err(|e| (LavaErrorKind::UnknownAccount, e))?;
+        match assert {
+            LavaAssert::Balance { lamports, .. } => Ok(Assertion::Lamports {
+                account,
+                lamports: *lamports,
+            }),
+            LavaAssert::TokenBalance { amount, .. } => Ok(Assertion::TokenAmount {
+                account,
+                amount: *amount,
+            }),
+            LavaAssert::Exists { .. } => Ok(Assertion::Exists {
+                account,
+                exists: true,
+            }),
+            LavaAssert::Closed { .. } => Ok(Assertion::Exists {
+                account,
+                exists: false,
+            }),
+            LavaAssert::Data {
+                account_type,
+                fields,
+                ..
+            } => {
+                let invalid = |e| (LavaErrorKind::InvalidAssert, e);
+                let Ok(DefinedType::Struct(layout)) = defined_type(idl, account_type) else {
+                    return Err(invalid(format!(
+                        "\"{}\" is not an account of \"{}\"",
+                        account_type, idl.name
+                    )));
+                };
+                let fields = fields
+                    .iter()
+                    .map(|(name, value)| {
+                        let (_, kind): &(String, InstructionType) = layout
+                            .iter()
+                            .find(|(f, _)| f == name)
+                            .ok_or(format!("\"{}\" has no field \"{}\"", account_type, name))
+                            .map_err(invalid)?;
+                        let value = self
+                            .arg_value(idl, kind, value)
+                            .map_err(|e| invalid(format!("field \"{}\": {}", name, e)))?;
+                        Ok((name.clone(), value))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Assertion::Data {
+                    account,
+                    program: idl.name.clone(),
+                    account_type: account_type.clone(),
+                    fields,
+                })
+            }
+        }
+    }
+}
+
+impl LavaConfig {
+    /// Checks `delta` and lowers it to the range the change must fall in.
+    pub(crate) fn delta(&self, delta: &LavaDelta) -> Result<Delta, (LavaErrorKind, String)> {
+        let account = self
+            .checked_account(&delta.account)
+            .map_err(|e| (LavaErrorKind::UnknownAccount, e))?;
+        let invalid = |e: &str| Err((LavaErrorKind::InvalidAssert, e.to_string()));
+        let (min, max) = match (delta.change, delta.fees, delta.min, delta.max) {
+            (Some(_), _, Some(_), _) | (Some(_), _, _, Some(_)) => {
+                return invalid("give either an exact change or a min and max, not both")
+            }
+            (None, None, None, None) => return invalid("give a change, a min or a max"),
+            (None, Some(_), _, _) => return invalid("fees only apply to an exact change"),
+            (Some(_), Some(_), _, _) if delta.kind == DeltaKind::Tokens => {
+                return invalid("fees are paid in lamports, not tokens")
+            }
+            (Some(change), fees, _, _) => (
+                Some(change - fees.unwrap_or_default() as i128),
+                Some(change),
+            ),
+            (None, None, min, max) => (min, max),
+        };
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                return invalid("min is greater than max");
+            }
+        }
+        Ok(Delta {
+            account,
+            tokens: delta.kind == DeltaKind::Tokens,
+            min,
+            max,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use serde_json::json;
+
+    use crate::codegen::{AccountRef, ArgValue, Assertion};
+    use crate::error::LavaErrorKind;
+
+    use super::{LavaAssert, LavaDelta};
+
+    fn assertion(assert: serde_json::Value) -> Result<Assertion, LavaErrorKind> {
+        let config = fixtures::config(&fixtures::escrow_make());
+        let assert: LavaAssert = serde_json::from_value(assert).unwrap();
+        config
+            .assertion(&config.idls[0], &assert)
+            .map_err(|(kind, _)| kind)
+    }
+
+    #[test]
+    fn checks_assertions() {
+        assert!(matches!(
+            assertion(json!({ "kind": "closed", "account": "Escrow" })),
+            Ok(Assertion::Exists { account: AccountRef::Address(a), exists: false }) if a == "escrow"
+        ));
+        let Ok(Assertion::Data { fields, .. }) = assertion(json!({
+            "kind": "data",
+            "account": "Escrow",
+            "type": "Escrow",
+            "fields": { "mintA": "TokenA", "amount": 1000000 }
+        })) else {
+            panic!("expected a data assertion");
+        };
+        assert_eq!(fields.len(), 2);
+
+        assert_eq!(
+            assertion(json!({ "kind": "balance", "account": "Makr", "lamports": 1 })).err(),
+            Some(LavaErrorKind::UnknownAccount)
+        );
+        assert_eq!(
+            assertion(json!({ "kind": "data", "account": "Escrow", "type": "Escrow", "fields": { "maker": "Maker" } })).err(),
+            Some(LavaErrorKind::InvalidAssert)
+        );
+    }
+
+    #[test]
+    fn lowers_deltas_to_ranges() {
+        let config = fixtures::config(&fixtures::escrow_make());
+        let delta = |delta| {
+            let delta: LavaDelta = serde_json::from_value(delta).unwrap();
+            config
+                .delta(&delta)
+                .map(|d| (d.tokens, d.min, d.max))
+                .map_err(|(kind, _)| kind)
+        };
+
+        assert_eq!(
+            delta(json!({ "account": "Maker", "change": -100, "fees": 5000 })),
+            Ok((false, Some(-5100), Some(-100)))
+        );
+        assert_eq!(
+            delta(json!({ "account": "Vault", "kind": "tokens", "min": 1 })),
+            Ok((true, Some(1), None))
+        );
+        assert_eq!(
+            delta(json!({ "account": "Vault", "kind": "tokens", "change": 1, "fees": 1 })),
+            Err(LavaErrorKind::InvalidAssert)
+        );
+        assert_eq!(
+            delta(json!({ "account": "Maker", "min": 2, "max": 1 })),
+            Err(LavaErrorKind::InvalidAssert)
+        );
+    }
+
+    #[test]
+    fn plans_assertions() {
+        let mut json = fixtures::escrow_make();
+        json["tests"][0]["assert"] = json!([
+            { "kind": "tokenBalance", "account": "Vault", "amount": 1000000 },
+            { "kind": "exists", "account": "Escrow" },
+            { "kind": "data", "account": "Escrow", "type": "Escrow", "fields": { "mintA": "TokenA", "amount": 1000000 } }
+        ]);
+        let plan = fixtures::config(&json).plan().unwrap();
+
+        let [vault, escrow, data] = &plan.tests[0].asserts[..] else {
+            panic!("expected three assertions");
+        };
+        assert!(matches!(
+            vault,
+            Assertion::TokenAmount { account: AccountRef::Address(a), amount: 1000000 } if a == "vault"
+        ));
+        assert!(matches!(
+            escrow,
+            Assertion::Exists { account: AccountRef::Address(a), exists: true } if a == "escrow"
+        ));
+        let Assertion::Data {
+            account,
+            program,
+            account_type,
+            fields,
+        } = data
+        else {
+            panic!("expected a data assertion");
+        };
+        assert_eq!(*account, AccountRef::Address("escrow".into()));
+        assert_eq!(
+            (program.as_str(), account_type.as_str()),
+            ("anchor_escrow", "Escrow")
+        );
+        assert!(matches!(
+            &fields[..],
+            [
+                (amount, ArgValue::Integer { value, bits: 64, .. }),
+                (mint, ArgValue::PublicKey(AccountRef::Keypair(token))),
+            ] if amount == "amount" && value == "1000000" && mint == "mintA" && token == "token_a"
+        ));
+    }
+
+    #[test]
+    fn plans_deltas() {
+        let mut json = fixtures::escrow_make();
+        json["tests"][0]["deltas"] = json!([
+            { "account": "Vault", "kind": "tokens", "change": 1000000 },
+            { "account": "Maker", "change": 0, "fees": 10000 }
+        ]);
+        let plan = fixtures::config(&json).plan().unwrap();
+
+        let deltas: Vec<_> = plan.tests[0]
+            .deltas
+            .iter()
+            .map(|d| (d.account.clone(), d.tokens, d.min, d.max))
+            .collect();
+        assert_eq!(
+            deltas,
+            [
+                (
+                    AccountRef::Address("vault".into()),
+                    true,
+                    Some(1000000),
+                    Some(1000000)
+                ),
+                (
+                    AccountRef::Keypair("maker".into()),
+                    false,
+                    Some(-10000),
+                    Some(0)
+                ),
+            ]
+        );
+    }
+}
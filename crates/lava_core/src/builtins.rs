@@ -0,0 +1,100 @@
+//! Programs and sysvars every cluster has. Tests use them without declaring
+//! them, and instruction accounts whose name or IDL address matches one are
+//! filled in without being mapped.
+
+use crate::check::account_key;
+
+/// A well-known program or sysvar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Builtin {
+    /// The name tests map it by, which is also what Anchor programs usually
+    /// call the account.
+    pub name: &'static str,
+    pub address: &'static str,
+    /// Other names programs commonly give the account.
+    pub aliases: &'static [&'static str],
+}
+
+pub const BUILTINS: [Builtin; 10] = [
+    Builtin {
+        name: "systemProgram",
+        address: "11111111111111111111111111111111",
+        aliases: &[],
+    },
+    Builtin {
+        name: "tokenProgram",
+        address: "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+        aliases: &[],
+    },
+    Builtin {
+        name: "token2022Program",
+        address: "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+        aliases: &["token2022", "tokenProgram2022"],
+    },
+    Builtin {
+        name: "associatedTokenProgram",
+        address: "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+        aliases: &[],
+    },
+    Builtin {
+        name: "memoProgram",
+        address: "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+        aliases: &["memo"],
+    },
+    Builtin {
+        name: "computeBudgetProgram",
+        address: "ComputeBudget111111111111111111111111111111",
+        aliases: &["computeBudget"],
+    },
+    Builtin {
+        name: "rent",
+        address: "SysvarRent111111111111111111111111111111111",
+        aliases: &["rentSysvar", "sysvarRent"],
+    },
+    Builtin {
+        name: "clock",
+        address: "SysvarC1ock11111111111111111111111111111111",
+        aliases: &["clockSysvar", "sysvarClock"],
+    },
+    Builtin {
+        name: "instructions",
+        address: "Sysvar1nstructions1111111111111111111111111",
+        aliases: &[
+            "instructionSysvar",
+            "instructionsSysvar",
+            "sysvarInstructions",
+        ],
+    },
+    Builtin {
+        name: "slotHashes",
+        address: "SysvarS1otHashes111111111111111111111111111",
+        aliases: &["slotHashesSysvar", "sysvarSlotHashes"],
+    },
+];
+
+/// The builtin `reference` names, by name, alias or address.
+pub fn builtin(reference: &str) -> Option<&'static Builtin> {
+    let key = account_key(reference);
+    BUILTINS.iter().find(|b| {
+        b.address == reference
+            || account_key(b.name) == key
+            || b.aliases.iter().any(|a| account_key(a) == key)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{builtin, BUILTINS};
+    use crate::lava_config::parse_address;
+
+    #[test]
+    fn finds_builtins_by_any_name() {
+        for b in BUILTINS {
+            assert_eq!(parse_address(b.address), Ok(b.address.to_string()));
+            assert_eq!(builtin(b.address), Some(&b));
+        }
+        assert_eq!(builtin("system_program").unwrap().name, "systemProgram");
+        assert_eq!(builtin("instructionSysvar").unwrap().name, "instructions");
+        assert_eq!(builtin("vault"), None);
+    }
+}
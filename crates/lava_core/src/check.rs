@@ -0,0 +1,895 @@
+use std::collections::BTreeMap;
+
+use convert_case::{Case, Casing};
+
+use crate::builtins::builtin;
+use crate::codegen::AccountRef;
+use crate::error::{LavaError, LavaErrorKind};
+use crate::idl::Idl;
+use crate::lava_config::{parse_address, LavaConfig};
+use crate::seeds::LavaSeed;
+
+/// Accounts are referenced by the identifier they are generated as, so
+/// `"Vault"` and `"vault"` name the same account.
+pub(crate) fn account_key(name: &str) -> String {
+    name.to_case(Case::Snake)
+}
+
+impl LavaConfig {
+    /// Checks that every cross reference in the workspace resolves, collecting
+    /// all problems rather than stopping at the first one.
+    pub(crate) fn check(&self) -> Result<(), LavaError> {
+        let mut errors = LavaError::default();
+        self.check_names(&mut errors);
+        self.check_programs(&mut errors);
+        self.check_mints(&mut errors);
+        self.check_atas(&mut errors);
+        self.check_pdas(&mut errors);
+        self.check_tests(&mut errors);
+        errors.into_result()
+    }
+
+    fn account_names(&self) -> Vec<&String> {
+        self.wallets
+            .keys()
+            .chain(self.mints.keys())
+            .chain(self.atas.keys())
+            .chain(self.programs.keys())
+            .chain(self.pdas.keys())
+            .collect()
+    }
+
+    pub(crate) fn has_account(&self, name: &str) -> bool {
+        let key = account_key(name);
+        self.account_names().iter().any(|n| account_key(n) == key)
+    }
+
+    /// The IDL of `program`, matched like every other account name.
+    pub(crate) fn idl(&self, program: &str) -> Option<&Idl> {
+        let key = account_key(program);
+        self.idls.iter().find(|i| account_key(&i.name) == key)
+    }
+
+    /// Generated tests get a handle for every program with an IDL, so only
+    /// those can be derived from or called.
+    fn has_program(&self, name: &str) -> bool {
+        self.idl(name).is_some()
+    }
+
+    fn check_names(&self, errors: &mut LavaError) {
+        let mut by_key: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+        for name in self.account_names() {
+            by_key.entry(account_key(name)).or_default().push(name);
+        }
+        // Names are listed in the order the config declares them, so the
+        // clash is reported on the later one.
+        for (key, names) in by_key {
+            if names.len() > 1 {
+                errors.push(
+                    LavaErrorKind::DuplicateName,
+                    format!("accounts.{}", names[1]),
+                    format!(
+                        "accounts {} all generate the identifier \"{}\"",
+                        names
+                            .iter()
+                            .map(|n| format!("\"{}\"", n))
+                            .collect::<Vec<String>>()
+                            .join(", "),
+                        key
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_programs(&self, errors: &mut LavaError) {
+        for program in self.programs.values() {
+            let idl = self.idl(&program.name);
+            let source = match (program.address.as_str(), idl) {
+                (_, Some(idl)) => self.program_source(idl).map(|_| ()),
+                ("program", None) => Ok(()),
+                ("idl", None) => Err("it has no IDL to take metadata.address from".to_string()),
+                (address, None) => parse_address(address).map(|_| ()),
+            };
+            if let Err(e) = source {
+                errors.push(
+                    LavaErrorKind::InvalidAddress,
+                    format!("accounts.{}.address", program.name),
+                    format!("program \"{}\" can't be loaded: {}", program.name, e),
+                );
+            }
+        }
+    }
+
+    fn check_atas(&self, errors: &mut LavaError) {
+        let mut atas = self.atas.values().collect::<Vec<_>>();
+        atas.sort_by(|a, b| a.name.cmp(&b.name));
+        for ata in atas {
+            let is_authority = |n: &String| account_key(n) == account_key(&ata.authority);
+            if !(self.wallets.keys().any(is_authority)
+                || self.pdas.keys().any(is_authority)
+                || account_key(&ata.authority) == "provider")
+            {
+                errors.push(
+                    LavaErrorKind::UnknownAccount,
+                    format!("accounts.{}.authority", ata.name),
+                    format!(
+                        "ATA \"{}\" is owned by \"{}\", which is not a declared wallet or PDA",
+                        ata.name, ata.authority
+                    ),
+                );
+            } else if self.is_deferred(&ata.authority) {
+                errors.push(
+                    LavaErrorKind::InvalidSeed,
+                    format!("accounts.{}.authority", ata.name),
+                    format!(
+                        "ATA \"{}\" is owned by \"{}\", which is seeded by an account field and only derived within tests",
+                        ata.name, ata.authority
+                    ),
+                );
+            }
+            match self
+                .mints
+                .values()
+                .find(|m| account_key(&m.name) == account_key(&ata.mint))
+            {
+                None => errors.push(
+                    LavaErrorKind::UnknownAccount,
+                    format!("accounts.{}.mint", ata.name),
+                    format!(
+                        "ATA \"{}\" holds \"{}\", which is not a declared mint",
+                        ata.name, ata.mint
+                    ),
+                ),
+                Some(mint) if self.token_program(ata) != mint.token_program => errors.push(
+                    LavaErrorKind::InvalidToken,
+                    format!("accounts.{}.tokenProgram", ata.name),
+                    format!(
+                        "ATA \"{}\" is under another token program than its mint \"{}\"",
+                        ata.name, mint.name
+                    ),
+                ),
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn check_mints(&self, errors: &mut LavaError) {
+        let mut mints = self.mints.values().collect::<Vec<_>>();
+        mints.sort_by(|a, b| a.name.cmp(&b.name));
+        for mint in mints {
+            let authorities = [
+                ("mintAuthority", Some(&mint.mint_authority)),
+                ("freezeAuthority", mint.freeze_authority.as_ref()),
+            ];
+            for (field, authority) in authorities {
+                let Some(authority) = authority else {
+                    continue;
+                };
+                let path = format!("accounts.{}.{}", mint.name, field);
+                if let Err(e) = self.authority(Some(authority)) {
+                    errors.push(
+                        LavaErrorKind::UnknownAccount,
+                        path,
+                        format!("mint \"{}\": {}", mint.name, e),
+                    );
+                } else if self.is_deferred(authority) {
+                    errors.push(
+                        LavaErrorKind::InvalidSeed,
+                        path,
+                        format!(
+                            "mint \"{}\" is created before \"{}\", which is seeded by an account field and only derived within tests",
+                            mint.name, authority
+                        ),
+                    );
+                }
+            }
+            for (i, extension) in mint.extensions.iter().enumerate() {
+                let path = format!("accounts.{}.extensions.{}", mint.name, i);
+                if mint.extensions[..i]
+                    .iter()
+                    .any(|e| e.kind() == extension.kind())
+                {
+                    errors.push(
+                        LavaErrorKind::InvalidToken,
+                        path,
+                        format!(
+                            "mint \"{}\" lists {} more than once",
+                            mint.name,
+                            extension.kind()
+                        ),
+                    );
+                } else if let Err((kind, e)) = self.extension(mint, extension) {
+                    errors.push(kind, path, format!("mint \"{}\": {}", mint.name, e));
+                }
+            }
+        }
+    }
+
+    fn check_pdas(&self, errors: &mut LavaError) {
+        let mut pdas = self.pdas.values().collect::<Vec<_>>();
+        pdas.sort_by(|a, b| a.name.cmp(&b.name));
+        for pda in pdas {
+            if !self.has_program(&pda.program) {
+                errors.push(
+                    LavaErrorKind::UnknownProgram,
+                    format!("accounts.{}.program", pda.name),
+                    format!(
+                        "PDA \"{}\" is derived from \"{}\", which has no IDL",
+                        pda.name, pda.program
+                    ),
+                );
+            }
+            let idl_seeds = self.idl_seeds(&pda.name);
+            let seeds = self.pda_seeds(pda);
+            if seeds.is_empty() {
+                let reason = match idl_seeds.iter().find_map(|s| s.seeds.as_ref().err()) {
+                    Some(e) => format!("its IDL seeds can't be used: {}", e),
+                    None => "no test maps it to an IDL account with `pda` seeds".to_string(),
+                };
+                errors.push(
+                    LavaErrorKind::InvalidSeed,
+                    format!("accounts.{}.seeds", pda.name),
+                    format!("PDA \"{}\" has no seeds, and {}", pda.name, reason),
+                );
+            }
+            for idl in &idl_seeds {
+                let Ok(expected) = &idl.seeds else {
+                    continue;
+                };
+                if let Some(i) = self.seed_mismatch(&seeds, expected) {
+                    let path = if pda.seeds.is_empty() {
+                        format!("tests.{}.accounts.{}", idl.test, idl.account)
+                    } else {
+                        format!("accounts.{}.seeds.{}", pda.name, i)
+                    };
+                    errors.push(
+                        LavaErrorKind::SeedMismatch,
+                        path,
+                        format!(
+                            "PDA \"{}\" seed {} doesn't match the IDL seeds of \"{}\" in test \"{}\": {}",
+                            pda.name,
+                            i,
+                            idl.account,
+                            idl.test,
+                            serde_json::to_string(expected).unwrap_or_default()
+                        ),
+                    );
+                }
+            }
+            for (i, seed) in seeds.iter().enumerate() {
+                if let LavaSeed::PublicKey(p) = seed {
+                    if !self.has_account(p) {
+                        errors.push(
+                            LavaErrorKind::UnknownAccount,
+                            format!("accounts.{}.seeds.{}", pda.name, i),
+                            format!(
+                                "PDA \"{}\" is seeded by \"{}\", which is not a declared account",
+                                pda.name, p
+                            ),
+                        );
+                        continue;
+                    }
+                }
+                if let Err((kind, e)) = self.seed(seed) {
+                    errors.push(
+                        kind,
+                        format!("accounts.{}.seeds.{}", pda.name, i),
+                        format!("PDA \"{}\" seed {}: {}", pda.name, i, e),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_tests(&self, errors: &mut LavaError) {
+        for t in &self.tests {
+            let Some(idl) = self.idl(&t.program_id) else {
+                errors.push(
+                    LavaErrorKind::UnknownProgram,
+                    format!("tests.{}.programId", t.name),
+                    format!(
+                        "test \"{}\" calls program \"{}\", which has no IDL",
+                        t.name, t.program_id
+                    ),
+                );
+                continue;
+            };
+            let Some(instruction) = idl.instructions.iter().find(|i| i.name == t.instruction)
+            else {
+                errors.push(
+                    LavaErrorKind::UnknownInstruction,
+                    format!("tests.{}.instruction", t.name),
+                    format!(
+                        "test \"{}\" calls \"{}\", which is not an instruction of \"{}\"",
+                        t.name, t.instruction, idl.name
+                    ),
+                );
+                continue;
+            };
+
+            if t.args.len() != instruction.args.len() {
+                errors.push(
+                    LavaErrorKind::ArgCount,
+                    format!("tests.{}.args", t.name),
+                    format!(
+                        "test \"{}\" passes {} args but \"{}\" takes {}",
+                        t.name,
+                        t.args.len(),
+                        instruction.name,
+                        instruction.args.len()
+                    ),
+                );
+            }
+            for (i, (value, arg)) in t.args.iter().zip(&instruction.args).enumerate() {
+                if let Err(e) = self.arg_value(idl, &arg.kind, value) {
+                    errors.push(
+                        LavaErrorKind::InvalidArg,
+                        format!("tests.{}.args.{}", t.name, i),
+                        format!("test \"{}\" arg \"{}\": {}", t.name, arg.name, e),
+                    );
+                }
+            }
+            for (i, assert) in t.asserts.iter().enumerate() {
+                if let Err((kind, e)) = self.assertion(idl, assert) {
+                    errors.push(
+                        kind,
+                        format!("tests.{}.assert.{}", t.name, i),
+                        format!("test \"{}\" assert {}: {}", t.name, i, e),
+                    );
+                }
+            }
+            for (i, delta) in t.deltas.iter().enumerate() {
+                if let Err((kind, e)) = self.delta(delta) {
+                    errors.push(
+                        kind,
+                        format!("tests.{}.deltas.{}", t.name, i),
+                        format!("test \"{}\" delta {}: {}", t.name, i, e),
+                    );
+                }
+            }
+            if let Some(Err(e)) = t.expect.as_ref().map(|e| e.resolve(idl)) {
+                errors.push(
+                    LavaErrorKind::UnknownError,
+                    format!("tests.{}.expect.error", t.name),
+                    format!("test \"{}\" expects an unknown error: {}", t.name, e),
+                );
+            }
+
+            let Some(accounts) = t.accounts.as_object() else {
+                errors.push(
+                    LavaErrorKind::InvalidAccounts,
+                    format!("tests.{}.accounts", t.name),
+                    format!(
+                        "test \"{}\" must map instruction accounts to account names",
+                        t.name
+                    ),
+                );
+                continue;
+            };
+            for (key, value) in accounts {
+                if !instruction.accounts.iter().any(|a| &a.name == key) {
+                    errors.push(
+                        LavaErrorKind::UnknownInstructionAccount,
+                        format!("tests.{}.accounts.{}", t.name, key),
+                        format!(
+                            "test \"{}\" maps \"{}\", which is not an account of \"{}\"",
+                            t.name, key, instruction.name
+                        ),
+                    );
+                }
+                let resolves = value.as_str().is_some_and(|v| match self.resolve(v) {
+                    AccountRef::Literal(address) => parse_address(&address).is_ok(),
+                    _ => true,
+                });
+                if !resolves {
+                    errors.push(
+                        LavaErrorKind::UnknownAccount,
+                        format!("tests.{}.accounts.{}", t.name, key),
+                        format!(
+                            "test \"{}\" maps \"{}\" to {}, which is not a declared account",
+                            t.name, key, value
+                        ),
+                    );
+                }
+            }
+            for account in instruction.accounts.iter().filter(|a| a.isSigner) {
+                let mapped = match accounts.get(&account.name) {
+                    Some(value) => value.as_str().map(|v| self.resolve(v)),
+                    None => self.autofill(idl, t, &instruction.name, &account.name),
+                };
+                let reason = match mapped {
+                    Some(AccountRef::Address(_)) => "a PDA or ATA",
+                    Some(AccountRef::Program(_) | AccountRef::Builtin(_)) => "a program",
+                    Some(AccountRef::Literal(address)) if parse_address(&address).is_ok() => {
+                        "an address lava holds no keypair for"
+                    }
+                    _ => continue,
+                };
+                errors.push(
+                    LavaErrorKind::InvalidSigner,
+                    format!("tests.{}.accounts.{}", t.name, account.name),
+                    format!(
+                        "test \"{}\" maps signer \"{}\" to {}, which can't sign",
+                        t.name, account.name, reason
+                    ),
+                );
+            }
+            for account in &instruction.accounts {
+                if accounts.contains_key(&account.name)
+                    || idl.is_optional(&instruction.name, &account.name)
+                    || self
+                        .autofill(idl, t, &instruction.name, &account.name)
+                        .is_some()
+                {
+                    continue;
+                }
+                errors.push(
+                    LavaErrorKind::UnmappedAccount,
+                    format!("tests.{}.accounts.{}", t.name, account.name),
+                    format!(
+                        "test \"{}\" doesn't map \"{}\", which \"{}\" requires",
+                        t.name, account.name, instruction.name
+                    ),
+                );
+            }
+            // A token program filled in by name follows the test's mints
+            // and ATAs, which must then agree on one.
+            let token_program = instruction.accounts.iter().find(|a| {
+                !accounts.contains_key(&a.name)
+                    && idl.fixed_address(&instruction.name, &a.name).is_none()
+                    && builtin(&a.name).is_some_and(|b| b.name == "tokenProgram")
+            });
+            if let (Some(account), Err(e)) = (token_program, self.test_token_program(t)) {
+                errors.push(
+                    LavaErrorKind::InvalidToken,
+                    format!("tests.{}.accounts.{}", t.name, account.name),
+                    format!(
+                        "test \"{}\" leaves out \"{}\", but {}; map it",
+                        t.name, account.name, e
+                    ),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{AccountRef, SetupStep};
+    use crate::error::LavaErrorKind;
+    use crate::fixtures;
+    use crate::{LavaConfig, LavaConfigJSON};
+    use serde_json::json;
+
+    fn kinds(workspace: serde_json::Value) -> Vec<(LavaErrorKind, String)> {
+        let json: LavaConfigJSON = serde_json::from_value(workspace).unwrap();
+        let err = LavaConfig::try_from(&json).unwrap_err();
+        err.downcast_ref::<crate::error::LavaError>()
+            .unwrap()
+            .issues()
+            .iter()
+            .map(|i| (i.kind, i.path.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn examples_pass() {
+        for example in [
+            fixtures::escrow_make(),
+            fixtures::escrow(),
+            fixtures::dice(),
+            fixtures::escrow_anchor_030(),
+        ] {
+            fixtures::config(&example);
+        }
+    }
+
+    #[test]
+    fn requires_every_unpinned_account() {
+        let mut workspace = fixtures::escrow();
+        let make = workspace["tests"][0]["accounts"].as_object_mut().unwrap();
+        make.remove("vault");
+        make.remove("systemProgram");
+        make["tokenProgram"] = json!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+        assert_eq!(
+            kinds(workspace),
+            vec![(
+                LavaErrorKind::UnmappedAccount,
+                "tests.Make.accounts.vault".to_string()
+            )]
+        );
+
+        // 0.30 IDLs pin program accounts to their address, and the token
+        // program is a builtin.
+        let mut workspace = fixtures::escrow_anchor_030();
+        workspace["tests"][0]["accounts"]
+            .as_object_mut()
+            .unwrap()
+            .remove("token_program");
+        let plan = fixtures::config(&workspace).plan().unwrap();
+        let token_program = plan.tests[0]
+            .accounts
+            .iter()
+            .find(|a| a.name == "token_program")
+            .unwrap();
+        assert_eq!(
+            token_program.account,
+            Some(AccountRef::Builtin("tokenProgram".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_signers_without_keypairs() {
+        let mut workspace = fixtures::escrow();
+        workspace["tests"][0]["accounts"]["maker"] = json!("Vault");
+        workspace["tests"][1]["accounts"]["taker"] = json!("tokenProgram");
+        let issues = kinds(workspace);
+        assert!(issues.contains(&(
+            LavaErrorKind::InvalidSigner,
+            "tests.Make.accounts.maker".to_string()
+        )));
+        assert!(issues.contains(&(
+            LavaErrorKind::InvalidSigner,
+            "tests.Take.accounts.taker".to_string()
+        )));
+    }
+
+    #[test]
+    fn checks_token_2022_extensions() {
+        let mut workspace = fixtures::escrow();
+        let accounts = workspace["accounts"].as_array_mut().unwrap();
+        accounts[2]["tokenProgram"] = json!("token2022");
+        accounts[2]["extensions"] = json!([
+            { "kind": "nonTransferable" },
+            { "kind": "transferFee", "feeBasisPoints": 10001, "maxFee": 1 },
+            { "kind": "tokenMetadata", "uri": "https://lava.dev/a.json" },
+            { "kind": "defaultAccountState", "state": "frozen" },
+            { "kind": "nonTransferable" },
+        ]);
+        accounts[3]["extensions"] = json!([{ "kind": "permanentDelegate", "delegate": "Maker" }]);
+        accounts[5]["tokenProgram"] = json!("token");
+        assert_eq!(
+            kinds(workspace),
+            vec![
+                (
+                    LavaErrorKind::InvalidToken,
+                    "accounts.Token B.extensions.0".to_string()
+                ),
+                (
+                    LavaErrorKind::InvalidToken,
+                    "accounts.TokenA.extensions.0".to_string()
+                ),
+                (
+                    LavaErrorKind::InvalidToken,
+                    "accounts.TokenA.extensions.1".to_string()
+                ),
+                (
+                    LavaErrorKind::InvalidToken,
+                    "accounts.TokenA.extensions.2".to_string()
+                ),
+                (
+                    LavaErrorKind::InvalidToken,
+                    "accounts.TokenA.extensions.3".to_string()
+                ),
+                (
+                    LavaErrorKind::InvalidToken,
+                    "accounts.TokenA.extensions.4".to_string()
+                ),
+                (
+                    LavaErrorKind::InvalidToken,
+                    "accounts.Maker ATA A.tokenProgram".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_funding_frozen_accounts() {
+        let mut workspace = fixtures::escrow();
+        let accounts = workspace["accounts"].as_array_mut().unwrap();
+        accounts[2]["tokenProgram"] = json!("token2022");
+        accounts[2]["freeze_authority"] = json!("Taker");
+        accounts[2]["extensions"] = json!([{ "kind": "defaultAccountState", "state": "frozen" }]);
+        assert_eq!(
+            kinds(workspace.clone()),
+            vec![(
+                LavaErrorKind::InvalidToken,
+                "accounts.TokenA.extensions.0".to_string()
+            )]
+        );
+
+        // Maker ATA A is the only ATA of TokenA with an amount.
+        workspace["accounts"][5]["amount"] = json!(0);
+        fixtures::config(&workspace);
+    }
+
+    #[test]
+    fn rejects_metadata_fields_nobody_can_write() {
+        let mut workspace = fixtures::escrow();
+        let accounts = workspace["accounts"].as_array_mut().unwrap();
+        accounts[2]["tokenProgram"] = json!("token2022");
+        accounts[2]["extensions"] = json!([
+            { "kind": "metadataPointer" },
+            { "kind": "tokenMetadata", "updateAuthority": "Escrow", "additional": { "color": "red" } },
+        ]);
+        assert_eq!(
+            kinds(workspace.clone()),
+            vec![(
+                LavaErrorKind::InvalidToken,
+                "accounts.TokenA.extensions.1".to_string()
+            )]
+        );
+
+        // Without fields to write, the escrow can hold the update authority.
+        workspace["accounts"][2]["extensions"][1]["additional"] = json!({});
+        fixtures::config(&workspace);
+    }
+
+    #[test]
+    fn fills_the_token_program_of_the_test() {
+        let mut workspace = fixtures::escrow();
+        for t in workspace["tests"].as_array_mut().unwrap() {
+            t["accounts"]
+                .as_object_mut()
+                .unwrap()
+                .remove("tokenProgram");
+        }
+        workspace["accounts"][2]["tokenProgram"] = json!("token2022");
+        // Both tests also map Token B, which is under the token program.
+        assert_eq!(
+            kinds(workspace.clone()),
+            vec![
+                (
+                    LavaErrorKind::InvalidToken,
+                    "tests.Make.accounts.tokenProgram".to_string()
+                ),
+                (
+                    LavaErrorKind::InvalidToken,
+                    "tests.Take.accounts.tokenProgram".to_string()
+                ),
+            ]
+        );
+
+        workspace["accounts"][3]["tokenProgram"] = json!("token2022");
+        let plan = fixtures::config(&workspace).plan().unwrap();
+        for t in &plan.tests {
+            let token_program = t.accounts.iter().find(|a| a.name == "tokenProgram");
+            assert_eq!(
+                token_program.unwrap().account,
+                Some(AccountRef::Builtin("token2022Program".to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn checks_mint_authorities() {
+        let mut workspace = fixtures::escrow();
+        let accounts = workspace["accounts"].as_array_mut().unwrap();
+        accounts[2]["mint_authority"] = json!("Nobody");
+        // Older workspaces wrote freeze authorities as wallets.
+        accounts[3]["freeze_authority"] = json!({ "name": "Taker", "balance": 10 });
+        assert_eq!(
+            kinds(workspace.clone()),
+            vec![(
+                LavaErrorKind::UnknownAccount,
+                "accounts.TokenA.mintAuthority".to_string()
+            )]
+        );
+        workspace["accounts"][2]["mint_authority"] = json!("Escrow");
+        let plan = fixtures::config(&workspace).plan().unwrap();
+        assert!(plan.setup.iter().any(|s| matches!(
+            s,
+            SetupStep::CreateMint { mint, freeze_authority: Some(AccountRef::Keypair(f)), .. }
+                if mint == "token_b" && f == "taker"
+        )));
+    }
+
+    #[test]
+    fn reports_accounts_with_the_same_name() {
+        let mut workspace = fixtures::escrow();
+        let accounts = workspace["accounts"].as_array_mut().unwrap();
+        accounts.push(json!({ "name": "Taker", "balance": 5, "kind": "wallet" }));
+        let json: LavaConfigJSON = serde_json::from_value(workspace).unwrap();
+        let err = LavaConfig::try_from(&json).unwrap_err();
+        let issues = err
+            .downcast_ref::<crate::error::LavaError>()
+            .unwrap()
+            .issues();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, LavaErrorKind::DuplicateName);
+        assert_eq!(issues[0].path, "accounts.Taker");
+        assert_eq!(
+            issues[0].message,
+            "accounts 1 and 11 are both named \"Taker\""
+        );
+    }
+
+    #[test]
+    fn reports_the_later_of_clashing_names() {
+        let mut workspace = fixtures::escrow();
+        let accounts = workspace["accounts"].as_array_mut().unwrap();
+        accounts.push(json!({ "name": "TOKEN_B", "balance": 1, "kind": "wallet" }));
+        let json: LavaConfigJSON = serde_json::from_value(workspace).unwrap();
+        let err = LavaConfig::try_from(&json).unwrap_err();
+        let issues = err
+            .downcast_ref::<crate::error::LavaError>()
+            .unwrap()
+            .issues();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].path, "accounts.TOKEN_B");
+        assert_eq!(
+            issues[0].message,
+            "accounts \"Token B\", \"TOKEN_B\" all generate the identifier \"token_b\""
+        );
+    }
+
+    #[test]
+    fn reports_unknown_kinds() {
+        let mut workspace = fixtures::escrow();
+        workspace["accounts"][1]["kind"] = json!("walet");
+        let issues = kinds(workspace);
+        assert_eq!(
+            issues[0],
+            (
+                LavaErrorKind::UnknownKind,
+                "accounts.Taker.kind".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn matches_programs_like_accounts() {
+        let workspace = fixtures::escrow();
+        let expected = fixtures::config(&workspace);
+
+        let mut renamed = workspace.clone();
+        renamed["tests"][0]["programId"] = json!("anchorEscrow");
+        renamed["tests"][1]["programId"] = json!("AnchorEscrow");
+        let renamed = fixtures::config(&renamed);
+        assert_eq!(
+            renamed.try_to_mocha().unwrap(),
+            expected.try_to_mocha().unwrap()
+        );
+        assert_eq!(
+            renamed.try_to_rust_tests().unwrap(),
+            expected.try_to_rust_tests().unwrap()
+        );
+    }
+
+    #[test]
+    fn reports_every_broken_reference() {
+        let mut workspace = fixtures::escrow();
+        let accounts = workspace["accounts"].as_array_mut().unwrap();
+        accounts.push(json!({ "name": "token_a", "balance": 1, "kind": "wallet" }));
+        accounts[5]["mint"] = json!("TokenC");
+        workspace["tests"][0]["accounts"]["vault"] = json!("Vualt");
+        workspace["tests"][0]["args"] = json!(["1"]);
+        workspace["tests"][0]["expect"] = json!({ "error": "ConstraintSeed" });
+        workspace["tests"][1]["instruction"] = json!("takeTwice");
+
+        assert_eq!(
+            kinds(workspace),
+            vec![
+                (LavaErrorKind::DuplicateName, "accounts.token_a".to_string()),
+                (
+                    LavaErrorKind::UnknownAccount,
+                    "accounts.Maker ATA A.mint".to_string()
+                ),
+                (LavaErrorKind::ArgCount, "tests.Make.args".to_string()),
+                (
+                    LavaErrorKind::UnknownError,
+                    "tests.Make.expect.error".to_string()
+                ),
+                (
+                    LavaErrorKind::UnknownAccount,
+                    "tests.Make.accounts.vault".to_string()
+                ),
+                (
+                    LavaErrorKind::UnknownInstruction,
+                    "tests.Take.instruction".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unresolvable_seeds() {
+        let mut workspace = fixtures::escrow();
+        let accounts = workspace["accounts"].as_array_mut().unwrap();
+        accounts[7]["authority"] = json!("Receipt");
+        accounts[10]["seeds"][2] = json!({ "kind": "arg", "test": "Make", "arg": "sed" });
+        accounts.push(json!({
+            "name": "Receipt",
+            "kind": "pda",
+            "program": "anchor_escrow",
+            "seeds": [
+                { "kind": "accountField", "account": "Escrow", "field": "maker" },
+                { "kind": "accountField", "account": "Maker", "field": "mintA" }
+            ]
+        }));
+
+        assert_eq!(
+            kinds(workspace),
+            vec![
+                (
+                    LavaErrorKind::InvalidSeed,
+                    "accounts.vault.authority".to_string()
+                ),
+                (
+                    LavaErrorKind::InvalidSeed,
+                    "accounts.Escrow.seeds.2".to_string()
+                ),
+                (
+                    LavaErrorKind::InvalidSeed,
+                    "accounts.Receipt.seeds.0".to_string()
+                ),
+                (
+                    LavaErrorKind::InvalidSeed,
+                    "accounts.Receipt.seeds.1".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn derives_and_cross_checks_idl_seeds() {
+        let mut workspace = fixtures::escrow();
+        let idl = &mut workspace["idls"][0]["instructions"];
+        idl[0]["accounts"][1]["pda"] = json!({ "seeds": [
+            { "kind": "const", "type": "string", "value": "escrow" },
+            { "kind": "account", "type": "publicKey", "path": "maker" },
+            { "kind": "arg", "type": "u64", "path": "seed" }
+        ] });
+        idl[2]["accounts"][2]["pda"] = json!({ "seeds": [
+            { "kind": "const", "type": "string", "value": "escrow" },
+            { "kind": "account", "type": "publicKey", "path": "maker" },
+            { "kind": "account", "type": "u64", "account": "Escrow", "path": "escrow.seed" }
+        ] });
+
+        let mut derived = workspace.clone();
+        derived["accounts"][10]
+            .as_object_mut()
+            .unwrap()
+            .remove("seeds");
+        let config = fixtures::config(&derived);
+        let seeds = config.pda_seeds(&config.pdas["Escrow"]);
+        assert_eq!(
+            serde_json::to_value(seeds).unwrap(),
+            json!([
+                { "kind": "String", "value": "escrow" },
+                { "kind": "Pubkey", "value": "Maker" },
+                { "kind": "arg", "test": "Make", "arg": "seed" }
+            ])
+        );
+
+        workspace["accounts"][10]["seeds"][2] = json!({ "kind": "u64", "value": 1 });
+        fixtures::config(&workspace);
+
+        workspace["accounts"][10]["seeds"][2] = json!({ "kind": "u32", "value": 1 });
+        assert_eq!(
+            kinds(workspace.clone()),
+            vec![(
+                LavaErrorKind::SeedMismatch,
+                "accounts.Escrow.seeds.2".to_string()
+            )]
+        );
+        workspace["accounts"][10]["seeds"]
+            .as_array_mut()
+            .unwrap()
+            .pop();
+        assert_eq!(
+            kinds(workspace),
+            vec![
+                (
+                    LavaErrorKind::SeedMismatch,
+                    "accounts.Escrow.seeds.2".to_string()
+                ),
+                (
+                    LavaErrorKind::SeedMismatch,
+                    "accounts.Escrow.seeds.2".to_string()
+                ),
+            ]
+        );
+    }
+}
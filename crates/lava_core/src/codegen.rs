@@ -0,0 +1,1392 @@
+//! Test generation. A [`LavaConfig`] is first lowered to a [`TestPlan`], in
+//! which every account name and arg is resolved, and a [`TestGenerator`]
+//! renders the plan. Backends outside this crate only need to implement
+//! [`TestGenerator`] and call [`LavaConfig::generate`].
+
+use convert_case::{Case, Casing};
+use soda_sol::structs::InstructionType;
+use wasm_bindgen::prelude::*;
+
+use crate::builtins::builtin;
+use crate::check::account_key;
+use crate::error::{LavaError, LavaErrorKind};
+use crate::idl::Idl;
+use crate::lava_config::{program_variable, LavaConfig, LavaTest, ProgramSource};
+use crate::seeds::{constant_name, LavaSeed};
+use crate::token::{funded_mint_len, mint_len, TokenProgram};
+
+pub mod bankrun;
+pub mod mocha;
+pub mod rust;
+
+pub use bankrun::Bankrun;
+pub use mocha::{Jest, Mocha, Vitest};
+pub use rust::RustProgramTest;
+
+/// A backend that renders a [`TestPlan`] as a test file.
+pub trait TestGenerator {
+    /// Reports what this backend can't render in a checked `plan`, like
+    /// accounts the TS clients would leave for Anchor to resolve.
+    fn validate(&self, _plan: &TestPlan) -> Result<(), LavaError> {
+        Ok(())
+    }
+
+    fn generate(&self, plan: &TestPlan) -> String;
+}
+
+/// A workspace with every reference resolved, in the order things are
+/// declared, set up and run in generated tests.
+#[derive(Debug, Clone)]
+pub struct TestPlan {
+    pub name: String,
+    pub programs: Vec<Program>,
+    /// Test args that PDAs are seeded by, declared before any account.
+    pub constants: Vec<Constant>,
+    /// Wallets, mints, PDAs and ATAs, each after the accounts it is derived
+    /// from.
+    pub accounts: Vec<Account>,
+    pub setup: Vec<SetupStep>,
+    pub tests: Vec<TestStep>,
+}
+
+impl TestPlan {
+    pub fn program(&self, name: &str) -> Option<&Program> {
+        self.programs
+            .iter()
+            .find(|p| account_key(&p.idl.name) == account_key(name))
+    }
+
+    /// Whether the tests create token accounts or pass the token or
+    /// associated token program.
+    pub fn uses_spl(&self) -> bool {
+        self.accounts
+            .iter()
+            .any(|a| matches!(a.kind, AccountKind::Mint | AccountKind::Ata { .. }))
+            || self.tests.iter().flat_map(|t| t.references()).any(|a| {
+                matches!(a, AccountRef::Builtin(name)
+                    if name == "tokenProgram"
+                        || name == "token2022Program"
+                        || name == "associatedTokenProgram")
+            })
+    }
+
+    /// Whether any mint, ATA or test of the plan is under Token-2022.
+    pub(crate) fn uses_token_2022(&self) -> bool {
+        self.setup.iter().any(|s| {
+            matches!(
+                s,
+                SetupStep::CreateMint {
+                    token_program: TokenProgram::Token2022,
+                    ..
+                }
+            )
+        }) || self.accounts.iter().any(|a| {
+            matches!(
+                a.kind,
+                AccountKind::Ata {
+                    token_program: TokenProgram::Token2022,
+                    ..
+                }
+            )
+        }) || self
+            .tests
+            .iter()
+            .flat_map(|t| t.references())
+            .any(|a| *a == TokenProgram::Token2022.builtin())
+    }
+
+    /// The mint authority `mint` is created with.
+    pub(crate) fn mint_authority(&self, mint: &str) -> AccountRef {
+        self.setup
+            .iter()
+            .find_map(|s| match s {
+                SetupStep::CreateMint {
+                    mint: m,
+                    mint_authority,
+                    ..
+                } if m == mint => Some(mint_authority.clone()),
+                _ => None,
+            })
+            .unwrap_or(AccountRef::Provider)
+    }
+}
+
+/// A program with an IDL, which tests get a handle to.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub source: ProgramSource,
+    pub idl: Idl,
+}
+
+/// A test arg held in one place, so the PDA derived from it and the call
+/// that passes it can't drift apart.
+#[derive(Debug, Clone)]
+pub struct Constant {
+    pub identifier: String,
+    /// The IDL name of the program the test calls.
+    pub program: String,
+    pub value: ArgValue,
+}
+
+#[derive(Debug, Clone)]
+pub struct Account {
+    /// The name the account is generated as.
+    pub identifier: String,
+    pub kind: AccountKind,
+}
+
+impl Account {
+    /// Whether the account is a PDA seeded by another account's data, which
+    /// is derived in each test that uses it instead of up front.
+    pub fn is_deferred(&self) -> bool {
+        match &self.kind {
+            AccountKind::Pda { seeds, .. } => seeds.iter().any(|s| matches!(s, Seed::Field { .. })),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AccountKind {
+    Wallet,
+    Mint,
+    /// Derived from `seeds` and the program named `program`.
+    Pda {
+        program: String,
+        seeds: Vec<Seed>,
+    },
+    Ata {
+        authority: AccountRef,
+        mint: AccountRef,
+        token_program: TokenProgram,
+    },
+}
+
+/// A resolved reference to an account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountRef {
+    /// A wallet or mint, generated as a keypair, by identifier.
+    Keypair(String),
+    /// A PDA or ATA, generated as an address, by identifier.
+    Address(String),
+    /// A program with an IDL, by IDL name.
+    Program(String),
+    /// The wallet paying for the tests.
+    Provider,
+    /// A program tests use without declaring it, e.g. `systemProgram`.
+    Builtin(String),
+    /// Anything not declared in the workspace, taken as a base58 address.
+    Literal(String),
+}
+
+impl AccountRef {
+    /// The name the account goes by in generated tests.
+    pub fn identifier(&self) -> &str {
+        match self {
+            AccountRef::Keypair(id) | AccountRef::Address(id) => id,
+            AccountRef::Program(name) | AccountRef::Builtin(name) | AccountRef::Literal(name) => {
+                name
+            }
+            AccountRef::Provider => "provider",
+        }
+    }
+}
+
+/// A PDA seed, as the bytes it contributes.
+#[derive(Debug, Clone)]
+pub enum Seed {
+    /// The UTF-8 bytes of a string.
+    String(String),
+    /// A little-endian integer, `bytes` wide, in decimal.
+    Integer {
+        value: String,
+        signed: bool,
+        bytes: u8,
+    },
+    Bytes(Vec<u8>),
+    Account(AccountRef),
+    /// A test arg of IDL type `kind`, read from the [`Constant`] named
+    /// `constant`. Public keys resolve to [`Seed::Account`] instead.
+    Arg {
+        constant: String,
+        kind: InstructionType,
+        value: ArgValue,
+    },
+    /// Field `field` of the account at `account`, decoded as the account
+    /// type `account_type` of `program`.
+    Field {
+        account: AccountRef,
+        program: String,
+        account_type: String,
+        /// The field name, as written in the IDL.
+        field: String,
+        kind: InstructionType,
+    },
+}
+
+/// State the tests start from.
+#[derive(Debug, Clone)]
+pub enum SetupStep {
+    FundWallet {
+        wallet: String,
+        sol: u64,
+    },
+    CreateMint {
+        mint: String,
+        decimals: u8,
+        mint_authority: AccountRef,
+        freeze_authority: Option<AccountRef>,
+        token_program: TokenProgram,
+        /// Token-2022 extensions, in the order they are initialized.
+        extensions: Vec<Extension>,
+    },
+    /// Creates the ATA `ata` of `owner` for `mint` and mints `amount` to it,
+    /// signed by the authority the mint is created with.
+    MintTo {
+        ata: String,
+        mint: String,
+        owner: AccountRef,
+        amount: u64,
+        token_program: TokenProgram,
+    },
+    /// Hands `mint` over to `authority` once it is funded. Mints whose
+    /// authority can't sign setup, like a PDA, are created with the
+    /// provider as their authority so it can mint to their ATAs.
+    SetMintAuthority {
+        mint: String,
+        authority: AccountRef,
+        token_program: TokenProgram,
+    },
+}
+
+/// A Token-2022 mint extension. All but [`Extension::TokenMetadata`] are
+/// initialized before the mint is.
+#[derive(Debug, Clone)]
+pub enum Extension {
+    TransferFee {
+        authority: AccountRef,
+        withdraw_authority: AccountRef,
+        basis_points: u16,
+        max_fee: u64,
+    },
+    InterestBearing {
+        authority: AccountRef,
+        rate: i16,
+    },
+    NonTransferable,
+    PermanentDelegate {
+        delegate: AccountRef,
+    },
+    MetadataPointer {
+        authority: AccountRef,
+        address: AccountRef,
+    },
+    TransferHook {
+        authority: AccountRef,
+        program: AccountRef,
+    },
+    DefaultAccountState {
+        frozen: bool,
+    },
+    /// Written once the mint is initialized, signed by its mint authority.
+    TokenMetadata {
+        update_authority: AccountRef,
+        name: String,
+        symbol: String,
+        uri: String,
+        additional: Vec<(String, String)>,
+    },
+}
+
+impl Extension {
+    /// Where the extension goes in the order Token-2022 requires.
+    fn order(&self) -> u8 {
+        match self {
+            Extension::TransferFee { .. } => 0,
+            Extension::InterestBearing { .. } => 1,
+            Extension::NonTransferable => 2,
+            Extension::PermanentDelegate { .. } => 3,
+            Extension::MetadataPointer { .. } => 4,
+            Extension::TransferHook { .. } => 5,
+            Extension::DefaultAccountState { .. } => 6,
+            Extension::TokenMetadata { .. } => 7,
+        }
+    }
+}
+
+/// A call to a program instruction.
+#[derive(Debug, Clone)]
+pub struct TestStep {
+    pub name: String,
+    /// The IDL name of the program called.
+    pub program: String,
+    /// The instruction name, as written in the IDL.
+    pub instruction: String,
+    /// Every account of the instruction, in IDL order.
+    pub accounts: Vec<AccountMeta>,
+    pub args: Vec<Arg>,
+    /// The error the call must fail with, if it isn't expected to succeed.
+    pub expect: Option<ExpectedError>,
+    /// Checks on account state once the call is done.
+    pub asserts: Vec<Assertion>,
+    /// Balances snapshotted before the call and checked for how much they
+    /// changed after it.
+    pub deltas: Vec<Delta>,
+}
+
+impl TestStep {
+    /// Every account the test maps, passes or checks, in that order and
+    /// with repeats.
+    pub fn references(&self) -> Vec<&AccountRef> {
+        fn passed<'a>(value: &'a ArgValue, out: &mut Vec<&'a AccountRef>) {
+            match value {
+                ArgValue::PublicKey(account) => out.push(account),
+                ArgValue::Option {
+                    value: Some(value), ..
+                } => passed(value, out),
+                ArgValue::Array { items, .. }
+                | ArgValue::Vec { items, .. }
+                | ArgValue::Set { items, .. }
+                | ArgValue::Tuple(items) => items.iter().for_each(|v| passed(v, out)),
+                ArgValue::Map { entries, .. } => entries.iter().for_each(|(k, v)| {
+                    passed(k, out);
+                    passed(v, out);
+                }),
+                ArgValue::Struct { fields, .. } => fields.iter().for_each(|(_, v)| passed(v, out)),
+                _ => {}
+            }
+        }
+        let mut out: Vec<&AccountRef> = self
+            .accounts
+            .iter()
+            .filter_map(|a| a.account.as_ref())
+            .collect();
+        self.args.iter().for_each(|a| passed(&a.value, &mut out));
+        for assert in &self.asserts {
+            match assert {
+                Assertion::Lamports { account, .. }
+                | Assertion::TokenAmount { account, .. }
+                | Assertion::Exists { account, .. } => out.push(account),
+                Assertion::Data {
+                    account, fields, ..
+                } => {
+                    out.push(account);
+                    fields.iter().for_each(|(_, v)| passed(v, &mut out));
+                }
+            }
+        }
+        out.extend(self.deltas.iter().map(|d| &d.account));
+        out
+    }
+
+    /// Whether the test maps, passes or checks the PDA or ATA `identifier`.
+    pub fn uses(&self, identifier: &str) -> bool {
+        self.references()
+            .into_iter()
+            .any(|account| matches!(account, AccountRef::Address(id) if id == identifier))
+    }
+}
+
+/// A bound on how much a balance changes over a call. A missing bound
+/// is open.
+#[derive(Debug, Clone)]
+pub struct Delta {
+    pub account: AccountRef,
+    /// Whether this tracks the tokens of a token account, rather than
+    /// lamports.
+    pub tokens: bool,
+    pub min: Option<i128>,
+    pub max: Option<i128>,
+}
+
+/// A check on account state after a call.
+#[derive(Debug, Clone)]
+pub enum Assertion {
+    Lamports {
+        account: AccountRef,
+        lamports: u64,
+    },
+    /// The amount held by a token account, in base units.
+    TokenAmount {
+        account: AccountRef,
+        amount: u64,
+    },
+    Exists {
+        account: AccountRef,
+        exists: bool,
+    },
+    /// Fields of a program account, decoded with the IDL of `program`.
+    Data {
+        account: AccountRef,
+        program: String,
+        account_type: String,
+        /// Field names as written in the IDL, with their expected values.
+        fields: Vec<(String, ArgValue)>,
+    },
+}
+
+/// An Anchor error a call is expected to fail with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedError {
+    pub code: u32,
+    /// The name the error is declared with, when the code is a known one.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AccountMeta {
+    /// The account name, as written in the IDL.
+    pub name: String,
+    /// What the test maps the account to, if anything.
+    pub account: Option<AccountRef>,
+    pub writable: bool,
+    pub signer: bool,
+    /// Whether the IDL lets the account be left out.
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Arg {
+    /// The arg name, as written in the IDL.
+    pub name: String,
+    pub value: ArgValue,
+    /// The [`Constant`] holding the value, when a PDA is seeded by it.
+    pub constant: Option<String>,
+}
+
+/// A test arg, checked against and shaped by its IDL type.
+#[derive(Debug, Clone)]
+pub enum ArgValue {
+    /// An integer in decimal.
+    Integer {
+        value: String,
+        signed: bool,
+        bits: u32,
+    },
+    Bool(bool),
+    String(String),
+    PublicKey(AccountRef),
+    Bytes(Vec<u8>),
+    /// `kind` is the type of the value inside the option.
+    Option {
+        kind: InstructionType,
+        value: Option<Box<ArgValue>>,
+    },
+    Array {
+        kind: InstructionType,
+        items: Vec<ArgValue>,
+    },
+    Vec {
+        kind: InstructionType,
+        items: Vec<ArgValue>,
+    },
+    /// A `HashSet`, or a `BTreeSet` if `ordered`.
+    Set {
+        kind: InstructionType,
+        items: Vec<ArgValue>,
+        ordered: bool,
+    },
+    Tuple(Vec<ArgValue>),
+    /// A `HashMap`, or a `BTreeMap` if `ordered`.
+    Map {
+        key: InstructionType,
+        value: InstructionType,
+        entries: Vec<(ArgValue, ArgValue)>,
+        ordered: bool,
+    },
+    /// A type from the IDL `types`, with fields named as in the IDL.
+    Struct {
+        name: String,
+        fields: Vec<(String, ArgValue)>,
+    },
+    Enum {
+        name: String,
+        variant: String,
+    },
+}
+
+/// Largest integer a JS number holds exactly; wider values go to `BN` as strings.
+pub(crate) const MAX_SAFE_INTEGER: u128 = (1 << 53) - 1;
+
+/// The expression for `account`'s public key, where `provider` is the
+/// expression for the provider's.
+pub(crate) fn key(account: &AccountRef, provider: &str) -> String {
+    match account {
+        AccountRef::Keypair(id) => format!("{}.publicKey", id),
+        AccountRef::Address(id) => id.clone(),
+        AccountRef::Program(name) => format!("{}.programId", program_variable(name)),
+        AccountRef::Provider => provider.to_string(),
+        AccountRef::Builtin(name) => match name.as_str() {
+            "systemProgram" => "SystemProgram.programId".to_string(),
+            "tokenProgram" => "TOKEN_PROGRAM_ID".to_string(),
+            "associatedTokenProgram" => "ASSOCIATED_TOKEN_PROGRAM_ID".to_string(),
+            "token2022Program" => "TOKEN_2022_PROGRAM_ID".to_string(),
+            _ => format!(
+                "new PublicKey(\"{}\")",
+                builtin(name).map_or(name.as_str(), |b| b.address)
+            ),
+        },
+        AccountRef::Literal(address) => format!("new PublicKey(\"{}\")", address),
+    }
+}
+
+fn seed(seed: &Seed, provider: &str) -> String {
+    match seed {
+        Seed::Account(account) => format!("{}.toBuffer()", key(account, provider)),
+        Seed::String(s) => format!(
+            "Buffer.from({}, \"utf-8\")",
+            serde_json::to_string(s).unwrap_or_default()
+        ),
+        // `toBuffer` writes the magnitude, so negatives go through two's
+        // complement first.
+        Seed::Integer {
+            value,
+            signed: true,
+            bytes,
+        } if value.starts_with('-') => format!(
+            "{}.toTwos({}).toBuffer(\"le\", {})",
+            bn(value),
+            *bytes as u32 * 8,
+            bytes
+        ),
+        Seed::Integer { value, bytes, .. } => {
+            format!("{}.toBuffer(\"le\", {})", bn(value), bytes)
+        }
+        Seed::Bytes(bytes) => hex_buffer(bytes),
+        Seed::Arg { constant, kind, .. } => seed_bytes(constant, kind),
+        Seed::Field {
+            account,
+            program,
+            account_type,
+            field,
+            kind,
+        } => seed_bytes(
+            &format!(
+                "(await {}.account.{}.fetch({})).{}",
+                program_variable(program),
+                account_type.to_case(Case::Camel),
+                key(account, provider),
+                field.to_case(Case::Camel)
+            ),
+            kind,
+        ),
+    }
+}
+
+/// The seed bytes of `value`, an expression of IDL type `kind` as Anchor's
+/// TS client holds it: a number or a `BN` for integers.
+fn seed_bytes(value: &str, kind: &InstructionType) -> String {
+    let int = |signed: bool, bytes: u32| {
+        if signed {
+            format!(
+                "new BN({}).toTwos({}).toBuffer(\"le\", {})",
+                value,
+                bytes * 8,
+                bytes
+            )
+        } else {
+            format!("new BN({}).toBuffer(\"le\", {})", value, bytes)
+        }
+    };
+    match kind {
+        InstructionType::U8 => int(false, 1),
+        InstructionType::I8 => int(true, 1),
+        InstructionType::U16 => int(false, 2),
+        InstructionType::I16 => int(true, 2),
+        InstructionType::U32 => int(false, 4),
+        InstructionType::I32 => int(true, 4),
+        InstructionType::U64 => int(false, 8),
+        InstructionType::I64 => int(true, 8),
+        InstructionType::U128 => int(false, 16),
+        InstructionType::I128 => int(true, 16),
+        InstructionType::Bool => format!("Buffer.from([{} ? 1 : 0])", value),
+        InstructionType::String => format!("Buffer.from({}, \"utf-8\")", value),
+        InstructionType::PublicKey => format!("{}.toBuffer()", value),
+        _ => format!("Buffer.from({})", value),
+    }
+}
+
+/// A `BN` of the decimal `value`, given as a string when a JS number
+/// can't hold it exactly.
+fn bn(value: &str) -> String {
+    let safe = value
+        .trim_start_matches('-')
+        .parse::<u128>()
+        .is_ok_and(|n| n <= MAX_SAFE_INTEGER);
+    if safe {
+        format!("new BN({})", value)
+    } else {
+        format!("new BN(\"{}\")", value)
+    }
+}
+
+fn hex_buffer(bytes: &[u8]) -> String {
+    format!(
+        r#"Buffer.from("{}", "hex")"#,
+        bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    )
+}
+
+/// Renders an arg as the literal Anchor's TS client expects.
+pub(crate) fn arg(value: &ArgValue) -> String {
+    let list = |items: &[ArgValue]| {
+        format!(
+            "[{}]",
+            items.iter().map(arg).collect::<Vec<String>>().join(", ")
+        )
+    };
+    match value {
+        ArgValue::Integer { value, bits, .. } if *bits <= 32 => value.clone(),
+        ArgValue::Integer { value, .. } => bn(value),
+        ArgValue::Bool(b) => b.to_string(),
+        ArgValue::String(s) => serde_json::to_string(s).unwrap_or_default(),
+        ArgValue::PublicKey(AccountRef::Literal(address)) => {
+            format!(r#"new PublicKey("{}")"#, address)
+        }
+        ArgValue::PublicKey(account) => format!(r#"accountsPublicKeys["{}"]"#, lookup(account)),
+        ArgValue::Bytes(bytes) => hex_buffer(bytes),
+        ArgValue::Option { value: None, .. } => "null".to_string(),
+        ArgValue::Option {
+            value: Some(value), ..
+        } => arg(value),
+        ArgValue::Array { items, .. }
+        | ArgValue::Vec { items, .. }
+        | ArgValue::Set { items, .. }
+        | ArgValue::Tuple(items) => list(items),
+        ArgValue::Map { entries, .. } => format!(
+            "new Map([{}])",
+            entries
+                .iter()
+                .map(|(k, v)| format!("[{}, {}]", arg(k), arg(v)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        ArgValue::Struct { fields, .. } => format!(
+            "{{ {} }}",
+            fields
+                .iter()
+                .map(|(field, v)| format!("{}: {}", field.to_case(Case::Camel), arg(v)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        ArgValue::Enum { variant, .. } => format!("{{ {}: {{}} }}", variant.to_case(Case::Camel)),
+    }
+}
+
+/// The `accountsPublicKeys` entry tests look `account` up in.
+pub(crate) fn lookup(account: &AccountRef) -> String {
+    match account {
+        AccountRef::Keypair(id) | AccountRef::Address(id) => id.clone(),
+        AccountRef::Provider => "provider".to_string(),
+        AccountRef::Program(name) | AccountRef::Builtin(name) | AccountRef::Literal(name) => {
+            name.to_case(Case::Snake)
+        }
+    }
+}
+
+/// Declarations of every constant, wallet, mint, PDA and ATA, one per
+/// constant and then one per account of `plan`, and the entries of the
+/// `accountsPublicKeys` object tests look them and any other account they
+/// use up in. PDAs are derived from the ID `program_id` gives for their
+/// program's name; deferred ones get a `derive_<pda>` function tests call
+/// instead.
+pub(crate) fn declarations(
+    plan: &TestPlan,
+    provider: &str,
+    program_id: impl Fn(&str) -> String,
+) -> (Vec<String>, Vec<String>) {
+    let mut accounts = vec![];
+    let constants = plan
+        .constants
+        .iter()
+        .map(|c| format!("const {} = {};", c.identifier, arg(&c.value)));
+    let declarations = plan.accounts.iter().map(|account| {
+        let id = &account.identifier;
+        match &account.kind {
+            AccountKind::Wallet | AccountKind::Mint => {
+                accounts.push(format!("{}: {}.publicKey", id, id));
+                format!("const {} = Keypair.generate();", id)
+            }
+            AccountKind::Pda { program, seeds } => {
+                let declaration = if account.is_deferred() {
+                    accounts.push(format!("{}: PublicKey.default", id));
+                    format!("const derive_{} = async () =>", id)
+                } else {
+                    accounts.push(id.clone());
+                    format!("const {} =", id)
+                };
+                format!(
+                    "{} PublicKey.findProgramAddressSync([{}], {})[0]",
+                    declaration,
+                    seeds
+                        .iter()
+                        .map(|s| seed(s, provider))
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    program_id(program)
+                )
+            }
+            AccountKind::Ata {
+                authority,
+                mint,
+                token_program,
+            } => {
+                accounts.push(id.clone());
+                let off_curve = !matches!(authority, AccountRef::Keypair(_) | AccountRef::Provider);
+                let owner = match (token_program, off_curve) {
+                    (TokenProgram::Token, false) => key(authority, provider),
+                    (TokenProgram::Token, true) => format!("{}, true", key(authority, provider)),
+                    (TokenProgram::Token2022, _) => format!(
+                        "{}, {}, TOKEN_2022_PROGRAM_ID",
+                        key(authority, provider),
+                        off_curve
+                    ),
+                };
+                format!(
+                    "const {} = getAssociatedTokenAddressSync({}, {});",
+                    id,
+                    key(mint, provider),
+                    owner
+                )
+            }
+        }
+    });
+    let declarations = constants.chain(declarations).collect();
+    // Programs, the provider and builtins tests use get an entry under the
+    // key `lookup` gives them.
+    let mut entries = vec![];
+    for account in plan.tests.iter().flat_map(|t| t.references()) {
+        let value = match account {
+            AccountRef::Program(name) => program_id(name),
+            AccountRef::Provider | AccountRef::Builtin(_) => key(account, provider),
+            _ => continue,
+        };
+        let entry = format!("{}: {}", lookup(account), value);
+        if !entries.contains(&entry) {
+            entries.push(entry);
+        }
+    }
+    accounts.extend(entries);
+    (declarations, accounts)
+}
+
+/// The instructions creating the Token-2022 mint of `step`: its account,
+/// sized and funded for its extensions, the extensions the mint must be
+/// initialized after, the mint, and then its token metadata. `rent` gives
+/// the expression for the rent of an account of a given size.
+pub(crate) fn token_2022_mint(
+    step: &SetupStep,
+    provider: &str,
+    rent: impl Fn(usize) -> String,
+) -> Vec<String> {
+    let SetupStep::CreateMint {
+        mint,
+        mint_authority,
+        extensions,
+        ..
+    } = step
+    else {
+        return vec![];
+    };
+    let address = format!("{}.publicKey", mint);
+    let key = |account: &AccountRef| key(account, provider);
+    let mut instructions = vec![format!(
+        "SystemProgram.createAccount({{
+        fromPubkey: {provider},
+        newAccountPubkey: {address},
+        lamports: {},
+        space: {},
+        programId: TOKEN_2022_PROGRAM_ID,
+      }})",
+        rent(funded_mint_len(extensions)),
+        mint_len(extensions)
+    )];
+    let mut metadata = vec![];
+    for extension in extensions {
+        instructions.push(match extension {
+            Extension::TransferFee {
+                authority,
+                withdraw_authority,
+                basis_points,
+                max_fee,
+            } => format!(
+                "createInitializeTransferFeeConfigInstruction({address}, {}, {}, {basis_points}, BigInt(\"{max_fee}\"), TOKEN_2022_PROGRAM_ID)",
+                key(authority),
+                key(withdraw_authority)
+            ),
+            Extension::InterestBearing { authority, rate } => format!(
+                "createInitializeInterestBearingMintInstruction({address}, {}, {rate}, TOKEN_2022_PROGRAM_ID)",
+                key(authority)
+            ),
+            Extension::NonTransferable => format!(
+                "createInitializeNonTransferableMintInstruction({address}, TOKEN_2022_PROGRAM_ID)"
+            ),
+            Extension::PermanentDelegate { delegate } => format!(
+                "createInitializePermanentDelegateInstruction({address}, {}, TOKEN_2022_PROGRAM_ID)",
+                key(delegate)
+            ),
+            Extension::MetadataPointer {
+                authority,
+                address: metadata_address,
+            } => format!(
+                "createInitializeMetadataPointerInstruction({address}, {}, {}, TOKEN_2022_PROGRAM_ID)",
+                key(authority),
+                key(metadata_address)
+            ),
+            Extension::TransferHook { authority, program } => format!(
+                "createInitializeTransferHookInstruction({address}, {}, {}, TOKEN_2022_PROGRAM_ID)",
+                key(authority),
+                key(program)
+            ),
+            Extension::DefaultAccountState { frozen } => format!(
+                "createInitializeDefaultAccountStateInstruction({address}, AccountState.{}, TOKEN_2022_PROGRAM_ID)",
+                if *frozen { "Frozen" } else { "Initialized" }
+            ),
+            Extension::TokenMetadata {
+                update_authority,
+                name,
+                symbol,
+                uri,
+                additional,
+            } => {
+                let string = |s: &String| serde_json::to_string(s).unwrap_or_default();
+                metadata.push(format!(
+                    r#"createInitializeInstruction({{
+        programId: TOKEN_2022_PROGRAM_ID,
+        metadata: {address},
+        updateAuthority: {},
+        mint: {address},
+        mintAuthority: {},
+        name: {},
+        symbol: {},
+        uri: {},
+      }})"#,
+                    key(update_authority),
+                    key(mint_authority),
+                    string(name),
+                    string(symbol),
+                    string(uri)
+                ));
+                for (field, value) in additional {
+                    metadata.push(format!(
+                        "createUpdateFieldInstruction({{ programId: TOKEN_2022_PROGRAM_ID, metadata: {address}, updateAuthority: {}, field: {}, value: {} }})",
+                        key(update_authority),
+                        string(field),
+                        string(value)
+                    ));
+                }
+                continue;
+            }
+        });
+    }
+    instructions.push(initialize_mint(step, provider));
+    instructions.extend(metadata);
+    instructions
+}
+
+/// The keypair update authorities of `extensions` that write additional
+/// token metadata fields, and so sign the mint's setup.
+pub(crate) fn field_writers(extensions: &[Extension]) -> Vec<String> {
+    extensions
+        .iter()
+        .filter_map(|e| match e {
+            Extension::TokenMetadata {
+                update_authority: AccountRef::Keypair(authority),
+                additional,
+                ..
+            } if !additional.is_empty() => Some(authority.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The trailing program id argument of `@solana/spl-token` instructions,
+/// which default to the token program.
+pub(crate) fn program_id(token_program: &TokenProgram) -> &'static str {
+    match token_program {
+        TokenProgram::Token => "",
+        TokenProgram::Token2022 => ", TOKEN_2022_PROGRAM_ID",
+    }
+}
+
+/// The instruction initializing the mint of a `CreateMint` step with its
+/// authorities.
+pub(crate) fn initialize_mint(step: &SetupStep, provider: &str) -> String {
+    let SetupStep::CreateMint {
+        mint,
+        decimals,
+        mint_authority,
+        freeze_authority,
+        token_program,
+        ..
+    } = step
+    else {
+        return "".to_string();
+    };
+    format!(
+        "createInitializeMint2Instruction({}.publicKey, {}, {}, {}{})",
+        mint,
+        decimals,
+        key(mint_authority, provider),
+        freeze_authority
+            .as_ref()
+            .map(|f| key(f, provider))
+            .unwrap_or("null".to_string()),
+        program_id(token_program)
+    )
+}
+
+impl LavaConfig {
+    /// What `reference` names, or a [`AccountRef::Literal`] if nothing in the
+    /// workspace does.
+    pub(crate) fn resolve(&self, reference: &str) -> AccountRef {
+        let key = account_key(reference);
+        let matches = |n: &String| account_key(n) == key;
+        if self.wallets.keys().chain(self.mints.keys()).any(matches) {
+            AccountRef::Keypair(key)
+        } else if self.pdas.keys().chain(self.atas.keys()).any(matches) {
+            AccountRef::Address(key)
+        } else if let Some(idl) = self.idl(reference) {
+            AccountRef::Program(idl.name.clone())
+        } else if key == "provider" {
+            AccountRef::Provider
+        } else if let Some(builtin) = builtin(reference) {
+            AccountRef::Builtin(builtin.name.to_string())
+        } else {
+            AccountRef::Literal(reference.to_string())
+        }
+    }
+
+    /// What `test` passes for `account` of `instruction` if it doesn't map
+    /// it: the address the IDL pins it to, or the builtin it is named after.
+    /// A token program left open is the one the test's mints and ATAs are
+    /// under.
+    pub(crate) fn autofill(
+        &self,
+        idl: &Idl,
+        test: &LavaTest,
+        instruction: &str,
+        account: &str,
+    ) -> Option<AccountRef> {
+        match idl.fixed_address(instruction, account) {
+            Some(address) => Some(match builtin(address) {
+                Some(builtin) => AccountRef::Builtin(builtin.name.to_string()),
+                None => AccountRef::Literal(address.to_string()),
+            }),
+            None => builtin(account).map(|b| match b.name {
+                "tokenProgram" => self.test_token_program(test).unwrap_or_default().builtin(),
+                name => AccountRef::Builtin(name.to_string()),
+            }),
+        }
+    }
+
+    /// Checks the workspace and lowers it to a [`TestPlan`]. Every test,
+    /// account and setup step is planned, or the reason it can't be is
+    /// returned.
+    pub fn plan(&self) -> Result<TestPlan, LavaError> {
+        self.check()?;
+        let fail = |path: String| move |(kind, e)| LavaError::single(kind, path, e);
+        let invalid = |kind: LavaErrorKind, path: String| move |e| LavaError::single(kind, path, e);
+
+        let mut constants: Vec<Constant> = vec![];
+        for pda in self.pdas.values() {
+            for (i, seed) in self.pda_seeds(pda).iter().enumerate() {
+                let path = format!("accounts.{}.seeds.{}", pda.name, i);
+                let LavaSeed::Arg { test, .. } = seed else {
+                    continue;
+                };
+                let Seed::Arg {
+                    constant, value, ..
+                } = self.seed(seed).map_err(fail(path.clone()))?
+                else {
+                    continue;
+                };
+                let t = self.tests.iter().find(|t| &t.name == test).ok_or_else(|| {
+                    LavaError::single(
+                        LavaErrorKind::InvalidSeed,
+                        path,
+                        format!("there is no test \"{}\"", test),
+                    )
+                })?;
+                if !constants.iter().any(|c| c.identifier == constant) {
+                    constants.push(Constant {
+                        identifier: constant,
+                        program: self.test_idl(t)?.name.clone(),
+                        value,
+                    });
+                }
+            }
+        }
+
+        let programs = self
+            .idls
+            .iter()
+            .map(|idl| {
+                Ok(Program {
+                    source: self.program_source(idl).map_err(invalid(
+                        LavaErrorKind::InvalidAddress,
+                        format!("accounts.{}.address", idl.name),
+                    ))?,
+                    idl: idl.clone(),
+                })
+            })
+            .collect::<Result<Vec<Program>, LavaError>>()?;
+
+        let mut accounts = vec![];
+        for name in self.declaration_order() {
+            let kind = if self.wallets.contains_key(name) {
+                AccountKind::Wallet
+            } else if self.mints.contains_key(name) {
+                AccountKind::Mint
+            } else if let Some(pda) = self.pdas.get(name) {
+                AccountKind::Pda {
+                    program: pda.program.clone(),
+                    seeds: self
+                        .pda_seeds(pda)
+                        .iter()
+                        .enumerate()
+                        .map(|(i, s)| {
+                            self.seed(s)
+                                .map_err(fail(format!("accounts.{}.seeds.{}", name, i)))
+                        })
+                        .collect::<Result<Vec<Seed>, LavaError>>()?,
+                }
+            } else if let Some(ata) = self.atas.get(name) {
+                AccountKind::Ata {
+                    authority: self.resolve(&ata.authority),
+                    mint: self.resolve(&ata.mint),
+                    token_program: self.token_program(ata),
+                }
+            } else {
+                continue;
+            };
+            accounts.push(Account {
+                identifier: name.to_case(Case::Snake),
+                kind,
+            });
+        }
+
+        let mut setup = vec![];
+        for wallet in self.wallets.values().filter(|w| w.balance > 0) {
+            setup.push(SetupStep::FundWallet {
+                wallet: wallet.name.to_case(Case::Snake),
+                sol: wallet.balance,
+            });
+        }
+        let mut handovers = vec![];
+        for mint in self.mints.values() {
+            let mut mint_authority = self.resolve(&mint.mint_authority);
+            let funded = self
+                .atas
+                .values()
+                .any(|a| a.amount > 0 && account_key(&a.mint) == account_key(&mint.name));
+            if funded
+                && !matches!(
+                    mint_authority,
+                    AccountRef::Keypair(_) | AccountRef::Provider
+                )
+            {
+                handovers.push(SetupStep::SetMintAuthority {
+                    mint: mint.name.to_case(Case::Snake),
+                    authority: mint_authority,
+                    token_program: mint.token_program,
+                });
+                mint_authority = AccountRef::Provider;
+            }
+            let mut extensions = mint
+                .extensions
+                .iter()
+                .enumerate()
+                .map(|(i, e)| {
+                    self.extension(mint, e)
+                        .map_err(fail(format!("accounts.{}.extensions.{}", mint.name, i)))
+                })
+                .collect::<Result<Vec<Extension>, LavaError>>()?;
+            extensions.sort_by_key(Extension::order);
+            setup.push(SetupStep::CreateMint {
+                mint: mint.name.to_case(Case::Snake),
+                decimals: mint.decimals,
+                mint_authority,
+                freeze_authority: mint.freeze_authority.as_ref().map(|f| self.resolve(f)),
+                token_program: mint.token_program,
+                extensions,
+            });
+        }
+        for ata in self.atas.values().filter(|a| a.amount > 0) {
+            setup.push(SetupStep::MintTo {
+                ata: ata.name.to_case(Case::Snake),
+                mint: ata.mint.to_case(Case::Snake),
+                owner: self.resolve(&ata.authority),
+                amount: ata.amount,
+                token_program: self.token_program(ata),
+            });
+        }
+        setup.extend(handovers);
+
+        let mut tests = vec![];
+        for t in &self.tests {
+            let idl = self.test_idl(t)?;
+            let instruction = idl
+                .instructions
+                .iter()
+                .find(|i| i.name == t.instruction)
+                .ok_or_else(|| {
+                    LavaError::single(
+                        LavaErrorKind::UnknownInstruction,
+                        format!("tests.{}.instruction", t.name),
+                        format!(
+                            "\"{}\" is not an instruction of \"{}\"",
+                            t.instruction, idl.name
+                        ),
+                    )
+                })?;
+            let mapped = t.accounts.as_object();
+            let accounts = instruction
+                .accounts
+                .iter()
+                .map(|a| AccountMeta {
+                    name: a.name.clone(),
+                    account: match mapped.and_then(|m| m.get(&a.name)) {
+                        Some(v) => v.as_str().map(|v| self.resolve(v)),
+                        None => self.autofill(idl, t, &instruction.name, &a.name),
+                    },
+                    writable: a.isMut,
+                    signer: a.isSigner,
+                    optional: idl.is_optional(&instruction.name, &a.name),
+                })
+                .collect();
+            let args = t
+                .args
+                .iter()
+                .zip(&instruction.args)
+                .enumerate()
+                .map(|(i, (value, arg))| {
+                    let constant = constant_name(&t.name, &arg.name);
+                    Ok(Arg {
+                        name: arg.name.clone(),
+                        value: self.arg_value(idl, &arg.kind, value).map_err(invalid(
+                            LavaErrorKind::InvalidArg,
+                            format!("tests.{}.args.{}", t.name, i),
+                        ))?,
+                        constant: constants
+                            .iter()
+                            .any(|c| c.identifier == constant)
+                            .then_some(constant),
+                    })
+                })
+                .collect::<Result<Vec<Arg>, LavaError>>()?;
+            tests.push(TestStep {
+                name: t.name.clone(),
+                program: idl.name.clone(),
+                instruction: instruction.name.clone(),
+                accounts,
+                args,
+                expect: t
+                    .expect
+                    .as_ref()
+                    .map(|e| {
+                        e.resolve(idl).map_err(invalid(
+                            LavaErrorKind::UnknownError,
+                            format!("tests.{}.expect.error", t.name),
+                        ))
+                    })
+                    .transpose()?,
+                asserts: t
+                    .asserts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, a)| {
+                        self.assertion(idl, a)
+                            .map_err(fail(format!("tests.{}.assert.{}", t.name, i)))
+                    })
+                    .collect::<Result<Vec<Assertion>, LavaError>>()?,
+                deltas: t
+                    .deltas
+                    .iter()
+                    .enumerate()
+                    .map(|(i, d)| {
+                        self.delta(d)
+                            .map_err(fail(format!("tests.{}.deltas.{}", t.name, i)))
+                    })
+                    .collect::<Result<Vec<Delta>, LavaError>>()?,
+            });
+        }
+
+        Ok(TestPlan {
+            name: self.name.clone(),
+            programs,
+            constants,
+            accounts,
+            setup,
+            tests,
+        })
+    }
+
+    /// The IDL of the program `test` calls.
+    fn test_idl(&self, test: &LavaTest) -> Result<&Idl, LavaError> {
+        self.idl(&test.program_id).ok_or_else(|| {
+            LavaError::single(
+                LavaErrorKind::UnknownProgram,
+                format!("tests.{}.programId", test.name),
+                format!("\"{}\" has no IDL", test.program_id),
+            )
+        })
+    }
+
+    /// Checks the workspace and renders it with `generator`, reporting every
+    /// problem found instead of generating a broken file.
+    pub fn try_generate(&self, generator: &impl TestGenerator) -> Result<String, LavaError> {
+        let plan = self.plan()?;
+        generator.validate(&plan)?;
+        Ok(generator.generate(&plan))
+    }
+}
+
+#[wasm_bindgen]
+impl LavaConfig {
+    /// Checks the workspace and renders it as a mocha test, reporting every
+    /// problem found instead of generating a broken file.
+    #[wasm_bindgen]
+    pub fn try_to_mocha(&self) -> Result<String, LavaError> {
+        self.try_generate(&Mocha)
+    }
+
+    /// The same as `try_to_mocha`, kept for existing callers.
+    #[wasm_bindgen]
+    pub fn to_mocha(&self) -> Result<String, LavaError> {
+        self.try_to_mocha()
+    }
+
+    #[wasm_bindgen]
+    pub fn try_to_jest(&self) -> Result<String, LavaError> {
+        self.try_generate(&Jest)
+    }
+
+    #[wasm_bindgen]
+    pub fn try_to_vitest(&self) -> Result<String, LavaError> {
+        self.try_generate(&Vitest)
+    }
+
+    #[wasm_bindgen]
+    pub fn try_to_bankrun(&self) -> Result<String, LavaError> {
+        self.try_generate(&Bankrun)
+    }
+
+    #[wasm_bindgen]
+    pub fn try_to_rust_tests(&self) -> Result<String, LavaError> {
+        self.try_generate(&RustProgramTest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    struct Names;
+
+    impl TestGenerator for Names {
+        fn generate(&self, plan: &TestPlan) -> String {
+            plan.tests
+                .iter()
+                .map(|t| format!("{}:{}", t.name, t.accounts.len()))
+                .collect::<Vec<String>>()
+                .join(",")
+        }
+    }
+
+    #[test]
+    fn plans_resolved_references() {
+        let plan = fixtures::config(&fixtures::escrow()).plan().unwrap();
+        let vault = plan
+            .accounts
+            .iter()
+            .find(|a| a.identifier == "vault")
+            .unwrap();
+        assert!(matches!(
+            &vault.kind,
+            AccountKind::Ata { authority: AccountRef::Address(a), mint: AccountRef::Keypair(m), .. }
+                if a == "escrow" && m == "token_a"
+        ));
+
+        let make = &plan.tests[0];
+        let meta = |name: &str| make.accounts.iter().find(|a| a.name == name).unwrap();
+        assert_eq!(
+            meta("maker").account,
+            Some(AccountRef::Keypair("maker".into()))
+        );
+        assert!(meta("maker").signer && meta("maker").writable);
+        assert_eq!(
+            meta("vault").account,
+            Some(AccountRef::Address("vault".into()))
+        );
+        assert_eq!(
+            meta("tokenProgram").account,
+            Some(AccountRef::Builtin("tokenProgram".into()))
+        );
+        assert!(matches!(
+            &make.args[0].value,
+            ArgValue::Integer { value, signed: false, bits: 64 } if value == "1"
+        ));
+    }
+
+    #[test]
+    fn reports_what_it_cannot_plan() {
+        let mut config = fixtures::config(&fixtures::escrow());
+        config.tests[1].instruction = "takeTwice".to_string();
+        let err = config.plan().unwrap_err();
+        assert_eq!(
+            err.issues()
+                .iter()
+                .map(|i| (i.kind, i.path.as_str()))
+                .collect::<Vec<_>>(),
+            [(LavaErrorKind::UnknownInstruction, "tests.Take.instruction")]
+        );
+    }
+
+    #[test]
+    fn fills_builtin_accounts() {
+        let plan = fixtures::config(&fixtures::escrow_make_unmapped())
+            .plan()
+            .unwrap();
+        let builtins = plan.tests[0]
+            .accounts
+            .iter()
+            .filter_map(|a| match &a.account {
+                Some(AccountRef::Builtin(name)) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            builtins,
+            ["tokenProgram", "associatedTokenProgram", "systemProgram"]
+        );
+
+        let mut dice = fixtures::dice();
+        dice["accounts"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::json!({ "name": "bet", "kind": "wallet", "balance": 1 }));
+        dice["tests"][0]["instruction"] = "resolveBet".into();
+        dice["tests"][0]["accounts"] = serde_json::json!({ "house": "house", "player": "player", "vault": "vault", "bet": "bet" });
+        dice["tests"][0]["args"] = serde_json::json!(["0x00"]);
+        let rust = fixtures::config(&dice)
+            .try_generate(&RustProgramTest)
+            .unwrap();
+        assert!(rust
+            .contains("AccountMeta::new_readonly(solana_sdk::sysvar::instructions::ID, false),"));
+    }
+
+    #[test]
+    fn runs_external_generators() {
+        assert_eq!(
+            fixtures::config(&fixtures::escrow())
+                .try_generate(&Names)
+                .unwrap(),
+            "Make:9,Take:12"
+        );
+    }
+}
@@ -0,0 +1,511 @@
+use convert_case::{Case, Casing};
+
+use crate::codegen::mocha::{self, StateReads};
+use crate::codegen::{
+    declarations, field_writers, key, token_2022_mint, AccountKind, AccountRef, Extension, Seed,
+    SetupStep, TestGenerator, TestPlan,
+};
+use crate::idl::IdlFormat;
+use crate::lava_config::{program_variable, ProgramSource};
+use crate::token::TokenProgram;
+
+/// TypeScript mocha tests that run against `solana-bankrun` instead of a
+/// validator. Wallet lamports, mints and funded ATAs are written straight
+/// into the bank with `setAccount` rather than sent as setup transactions,
+/// except for Token-2022 ones, whose extensions are created by the program.
+pub struct Bankrun;
+
+impl TestGenerator for Bankrun {
+    fn generate(&self, plan: &TestPlan) -> String {
+        let mut import_program_types = vec![];
+        let mut declare_programs = vec![];
+        let mut extra_programs = vec![];
+        let mut load_programs = vec![];
+
+        for program in &plan.programs {
+            let idl = &program.idl;
+            let program_name = idl.name.to_case(Case::Snake);
+            let program_type = idl.name.to_case(Case::Pascal);
+            let variable = program_variable(&idl.name);
+            match &program.source {
+                // `startAnchor` loads every program in Anchor.toml by itself.
+                ProgramSource::Workspace => {
+                    import_program_types.push(format!(
+                        r#"import {{ {} }} from "../target/types/{}";"#,
+                        program_type, program_name
+                    ));
+                    let address = match idl.format {
+                        IdlFormat::Legacy => "metadata.address",
+                        IdlFormat::Spec => "address",
+                    };
+                    declare_programs.push(format!(
+                        r#"    const {program_name}_idl = require("../target/idl/{program_name}.json");
+    const {program_name}_address = new PublicKey({program_name}_idl.{address});
+    let {variable}: Program<{program_type}>;"#
+                    ));
+                    load_programs.push(format!(
+                        "        {variable} = {};",
+                        mocha::new_program(idl, &program_name, &format!("<{}>", program_type))
+                    ));
+                }
+                // Anything else is read from tests/fixtures/<name>.so.
+                ProgramSource::Address(address) => {
+                    declare_programs.push(format!(
+                        r#"    const {program_name}_address = new PublicKey("{address}");
+    const {program_name}_idl = {} as anchor.Idl;
+    let {variable}: Program;"#,
+                        serde_json::to_string_pretty(&mocha::embedded_idl(idl, address))
+                            .unwrap_or_default()
+                    ));
+                    extra_programs.push(format!(
+                        r#"{{ name: "{program_name}", programId: {program_name}_address }}"#
+                    ));
+                    load_programs.push(format!(
+                        "        {variable} = {};",
+                        mocha::new_program(idl, &program_name, "")
+                    ));
+                }
+            }
+        }
+
+        let (declarations, accounts) = declarations(plan, "provider.wallet.publicKey", |program| {
+            format!("{}_address", program.to_case(Case::Snake))
+        });
+        // The provider only exists once `before` has started the bank, so
+        // addresses derived from it are assigned there.
+        let from_provider = from_provider(plan);
+        let (constants, declarations) = declarations.split_at(plan.constants.len());
+        let mut accounts_declarations = constants.to_vec();
+        let mut provider_accounts = vec![];
+        for (account, declaration) in plan.accounts.iter().zip(declarations) {
+            if from_provider.contains(&account.identifier.as_str()) {
+                accounts_declarations.push(format!("let {}: PublicKey;", account.identifier));
+                provider_accounts.push(format!(
+                    "        {}",
+                    declaration.strip_prefix("const ").unwrap_or(declaration)
+                ));
+            } else {
+                accounts_declarations.push(declaration.clone());
+            }
+        }
+        let spl = plan.uses_spl();
+
+        let import_spl = if spl {
+            r#"import {
+    ACCOUNT_SIZE,
+    ASSOCIATED_TOKEN_PROGRAM_ID,
+    AccountLayout,
+    AccountState,
+    MINT_SIZE,
+    MintLayout,
+    TOKEN_PROGRAM_ID,
+    getAssociatedTokenAddressSync,
+} from "@solana/spl-token";"#
+        } else {
+            ""
+        };
+        let set_token_accounts = if spl {
+            r#"
+    const setMint = (
+        address: PublicKey,
+        mintAuthority: PublicKey,
+        freezeAuthority: PublicKey | null,
+        decimals: number,
+        supply: bigint
+    ) => {
+        const data = Buffer.alloc(MINT_SIZE);
+        MintLayout.encode(
+            {
+                mintAuthorityOption: 1,
+                mintAuthority,
+                supply,
+                decimals,
+                isInitialized: true,
+                freezeAuthorityOption: freezeAuthority ? 1 : 0,
+                freezeAuthority: freezeAuthority ?? PublicKey.default,
+            },
+            data
+        );
+        context.setAccount(address, {
+            lamports: LAMPORTS_PER_SOL,
+            data,
+            owner: TOKEN_PROGRAM_ID,
+            executable: false,
+        });
+    };
+
+    const setTokenAccount = (
+        address: PublicKey,
+        mint: PublicKey,
+        owner: PublicKey,
+        amount: bigint
+    ) => {
+        const data = Buffer.alloc(ACCOUNT_SIZE);
+        AccountLayout.encode(
+            {
+                mint,
+                owner,
+                amount,
+                delegateOption: 0,
+                delegate: PublicKey.default,
+                state: AccountState.Initialized,
+                isNativeOption: 0,
+                isNative: BigInt(0),
+                delegatedAmount: BigInt(0),
+                closeAuthorityOption: 0,
+                closeAuthority: PublicKey.default,
+            },
+            data
+        );
+        context.setAccount(address, {
+            lamports: LAMPORTS_PER_SOL,
+            data,
+            owner: TOKEN_PROGRAM_ID,
+            executable: false,
+        });
+    };
+"#
+        } else {
+            ""
+        };
+
+        let setup = setup(plan).join("\n");
+        let user_defined_tests = mocha::tests(plan, ".rpc()");
+        let import_program_types = import_program_types.join("\n");
+        let declare_programs = declare_programs.join("\n");
+        let extra_programs = extra_programs.join(", ");
+        let load_programs = load_programs.join("\n");
+        let accounts_declarations = accounts_declarations.join("\n");
+        let provider_accounts = provider_accounts.join("\n");
+        let accounts = accounts.join(",\n            ");
+        let name = &plan.name;
+        let expect_anchor_error = mocha::expect_anchor_error(plan);
+        let reads = StateReads {
+            lamports: "context.banksClient.getBalance(address)",
+            token_amount:
+                "AccountLayout.decode((await context.banksClient.getAccount(address))!.data).amount",
+            exists: "(await context.banksClient.getAccount(address)) !== null",
+            token_import: if spl {
+                ""
+            } else {
+                "import { AccountLayout } from \"@solana/spl-token\";"
+            },
+        };
+        let state_imports = mocha::state_imports(plan, &reads);
+        let state_helpers = mocha::state_helpers(plan, &reads);
+        let mut imported = vec![
+            "ASSOCIATED_TOKEN_PROGRAM_ID",
+            "AccountState",
+            "TOKEN_PROGRAM_ID",
+            "getAssociatedTokenAddressSync",
+        ];
+        // Only Token-2022 mints are handed over by a transaction.
+        if !plan.setup.iter().any(|s| {
+            matches!(
+                s,
+                SetupStep::SetMintAuthority {
+                    token_program: TokenProgram::Token2022,
+                    ..
+                }
+            )
+        }) {
+            imported.extend(["AuthorityType", "createSetAuthorityInstruction"]);
+        }
+        let token_imports = mocha::token_imports(plan, &imported);
+
+        format!(
+            r#"
+import * as anchor from "@coral-xyz/anchor";
+import {{ Program, BN }} from "@coral-xyz/anchor";
+import {{ Keypair, LAMPORTS_PER_SOL, PublicKey, SystemProgram }} from "@solana/web3.js";
+import {{ BankrunProvider }} from "anchor-bankrun";
+import {{ ProgramTestContext, startAnchor }} from "solana-bankrun";
+{import_spl}{token_imports}{state_imports}
+{import_program_types}
+
+describe("{name}", () => {{
+    let context: ProgramTestContext;
+    let provider: BankrunProvider;
+
+{declare_programs}
+{expect_anchor_error}{state_helpers}
+// Accounts
+{accounts_declarations}
+    let accountsPublicKeys: Record<string, PublicKey>;
+{set_token_accounts}
+    before(async () => {{
+        context = await startAnchor("", [{extra_programs}], []);
+        provider = new BankrunProvider(context);
+{load_programs}
+{provider_accounts}
+        accountsPublicKeys = {{
+            {accounts}
+        }};
+
+{setup}
+    }});
+
+{user_defined_tests}
+}});"#
+        )
+    }
+}
+
+/// The PDAs and ATAs derived from the provider's key, directly or through
+/// another account. Deferred PDAs are derived in tests and aren't included.
+fn from_provider(plan: &TestPlan) -> Vec<&str> {
+    let mut accounts: Vec<&str> = vec![];
+    for account in plan.accounts.iter().filter(|a| !a.is_deferred()) {
+        let derived_from = match &account.kind {
+            AccountKind::Pda { seeds, .. } => seeds
+                .iter()
+                .filter_map(|s| match s {
+                    Seed::Account(a) => Some(a),
+                    _ => None,
+                })
+                .collect(),
+            AccountKind::Ata {
+                authority, mint, ..
+            } => vec![authority, mint],
+            AccountKind::Wallet | AccountKind::Mint => vec![],
+        };
+        let from_provider = derived_from.iter().any(|a| match a {
+            AccountRef::Provider => true,
+            AccountRef::Address(id) => accounts.contains(&id.as_str()),
+            _ => false,
+        });
+        if from_provider {
+            accounts.push(&account.identifier);
+        }
+    }
+    accounts
+}
+
+/// `setAccount` calls giving wallets their lamports, creating every mint
+/// with its final authority and the supply of the ATAs it funds, and filling
+/// those ATAs. Token-2022 mints and ATAs are sent as transactions instead, as
+/// are Token-2022 mints handed over to an authority that can't sign.
+fn setup(plan: &TestPlan) -> Vec<String> {
+    let provider = "provider.wallet.publicKey";
+    let mut setup = vec![];
+
+    for step in &plan.setup {
+        match step {
+            SetupStep::FundWallet { wallet, sol } => setup.push(format!(
+                r#"        context.setAccount({}.publicKey, {{
+            lamports: {} * LAMPORTS_PER_SOL,
+            data: Buffer.alloc(0),
+            owner: SystemProgram.programId,
+            executable: false,
+        }});"#,
+                wallet, sol
+            )),
+            SetupStep::CreateMint {
+                mint,
+                mint_authority,
+                token_program: TokenProgram::Token2022,
+                extensions,
+                ..
+            } => {
+                let rent = |len| {
+                    format!(
+                        "Number((await context.banksClient.getRent()).minimumBalance(BigInt({})))",
+                        len
+                    )
+                };
+                let mut signers = vec![mint.clone()];
+                // Token metadata is initialized by the mint authority.
+                if let AccountRef::Keypair(authority) = mint_authority {
+                    if extensions
+                        .iter()
+                        .any(|e| matches!(e, Extension::TokenMetadata { .. }))
+                    {
+                        signers.push(authority.clone());
+                    }
+                }
+                for authority in field_writers(extensions) {
+                    if !signers.contains(&authority) {
+                        signers.push(authority);
+                    }
+                }
+                setup.push(send(&token_2022_mint(step, provider, rent), &signers));
+            }
+            SetupStep::MintTo {
+                mint,
+                token_program: TokenProgram::Token2022,
+                ..
+            } => {
+                let signers = match plan.mint_authority(mint) {
+                    AccountRef::Keypair(authority) => vec![authority],
+                    _ => vec![],
+                };
+                setup.push(send(&mocha::mint_to(plan, step, provider), &signers));
+            }
+            SetupStep::CreateMint {
+                mint,
+                decimals,
+                mint_authority,
+                freeze_authority,
+                ..
+            } => {
+                let supply: u128 = plan
+                    .setup
+                    .iter()
+                    .filter_map(|s| match s {
+                        SetupStep::MintTo {
+                            mint: m, amount, ..
+                        } if m == mint => Some(*amount as u128),
+                        _ => None,
+                    })
+                    .sum();
+                // The supply is written directly, so the mint can start out
+                // with the authority it would be handed over to.
+                let mint_authority = plan
+                    .setup
+                    .iter()
+                    .find_map(|s| match s {
+                        SetupStep::SetMintAuthority {
+                            mint: m, authority, ..
+                        } if m == mint => Some(authority),
+                        _ => None,
+                    })
+                    .unwrap_or(mint_authority);
+                setup.push(format!(
+                    r#"        setMint({}.publicKey, {}, {}, {}, BigInt("{}"));"#,
+                    mint,
+                    key(mint_authority, provider),
+                    freeze_authority
+                        .as_ref()
+                        .map(|f| key(f, provider))
+                        .unwrap_or("null".to_string()),
+                    decimals,
+                    supply
+                ));
+            }
+            SetupStep::SetMintAuthority {
+                token_program: TokenProgram::Token2022,
+                ..
+            } => setup.push(send(&[mocha::set_mint_authority(step, provider)], &[])),
+            SetupStep::SetMintAuthority { .. } => {}
+            SetupStep::MintTo {
+                ata,
+                mint,
+                owner,
+                amount,
+                ..
+            } => setup.push(format!(
+                r#"        setTokenAccount({}, {}.publicKey, {}, BigInt("{}"));"#,
+                ata,
+                mint,
+                key(owner, provider),
+                amount
+            )),
+        }
+    }
+
+    setup
+}
+
+/// Sends `instructions` from the provider, also signed by the `signers`
+/// keypairs.
+fn send(instructions: &[String], signers: &[String]) -> String {
+    format!(
+        "        await provider.sendAndConfirm(new anchor.web3.Transaction().add(\n            {}\n        ), [{}]);",
+        instructions.join(",\n            "),
+        signers.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::setup;
+    use crate::codegen::Bankrun;
+    use crate::fixtures;
+
+    #[test]
+    fn orders_setup() {
+        let mut json = fixtures::escrow();
+        json["accounts"][2]["mint_authority"] = "Escrow".into();
+        json["accounts"][2]["freeze_authority"] = "Taker".into();
+        json["accounts"][3]["tokenProgram"] = "token2022".into();
+        json["accounts"][3]["mint_authority"] = "Escrow".into();
+        let setup = setup(&fixtures::config(&json).plan().unwrap());
+
+        // The first call of each step, or of the transaction it sends.
+        let calls: Vec<&str> = setup
+            .iter()
+            .map(|step| {
+                let mut lines = step.lines().map(str::trim);
+                let first = lines.next().unwrap();
+                match first.strip_prefix("await provider.sendAndConfirm(") {
+                    Some(_) => lines.next().unwrap(),
+                    None => first,
+                }
+            })
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                "context.setAccount(maker.publicKey, {",
+                "context.setAccount(taker.publicKey, {",
+                r#"setMint(token_a.publicKey, escrow, taker.publicKey, 6, BigInt("1000000000"));"#,
+                "SystemProgram.createAccount({",
+                r#"setTokenAccount(maker_ata_a, token_a.publicKey, maker.publicKey, BigInt("1000000000"));"#,
+                "createAssociatedTokenAccountIdempotentInstruction(provider.wallet.publicKey, taker_ata_b, taker.publicKey, token_b.publicKey, TOKEN_2022_PROGRAM_ID),",
+                "createSetAuthorityInstruction(token_b.publicKey, provider.wallet.publicKey, AuthorityType.MintTokens, escrow, [], TOKEN_2022_PROGRAM_ID)",
+            ]
+        );
+        // Token-2022 mints are minted to by the provider before the handover.
+        assert!(setup[3].contains(
+            "createInitializeMint2Instruction(token_b.publicKey, 6, provider.wallet.publicKey, null, TOKEN_2022_PROGRAM_ID)"
+        ));
+        assert!(setup[5].contains(
+            "createMintToInstruction(token_b.publicKey, taker_ata_b, provider.wallet.publicKey, 1000000000, [], TOKEN_2022_PROGRAM_ID)"
+        ));
+    }
+
+    #[test]
+    fn derives_provider_accounts_in_before() {
+        let mut json = fixtures::escrow();
+        json["accounts"][5]["authority"] = "provider".into();
+        let bankrun = fixtures::config(&json).try_generate(&Bankrun).unwrap();
+
+        // Nothing reads the provider before `before` has assigned it.
+        let before = bankrun.find("before(").unwrap();
+        let reads = bankrun
+            .match_indices("provider.wallet.publicKey")
+            .map(|(i, _)| i);
+        assert!(reads.clone().count() > 0);
+        for read in reads {
+            assert!(read > before);
+        }
+        // The provider's ATA is declared up front for tests to use and
+        // derived once the provider exists.
+        let declaration = bankrun.find("let maker_ata_a: PublicKey;").unwrap();
+        let derivation = bankrun.find("        maker_ata_a = ").unwrap();
+        assert!(declaration < before && before < derivation);
+        assert!(bankrun.find("let accountsPublicKeys").unwrap() < before);
+        assert!(before < bankrun.find("accountsPublicKeys = {").unwrap());
+        assert!(bankrun.contains("const maker = Keypair.generate();"));
+    }
+
+    #[test]
+    fn renders_bankrun_test() {
+        let bankrun = fixtures::config(&fixtures::escrow())
+            .try_generate(&Bankrun)
+            .unwrap();
+        for expected in [
+            r#"context = await startAnchor("", [], []);"#,
+            "anchor_escrow_program = new Program<AnchorEscrow>(anchor_escrow_idl, anchor_escrow_address, provider);",
+            "PublicKey.findProgramAddressSync([Buffer.from(\"escrow\", \"utf-8\"), maker.publicKey.toBuffer(), new BN(1).toBuffer(\"le\", 8)], anchor_escrow_address)[0]",
+            "lamports: 10 * LAMPORTS_PER_SOL,",
+            r#"setMint(token_a.publicKey, provider.wallet.publicKey, null, 6, BigInt("1000000000"));"#,
+            r#"setTokenAccount(maker_ata_a, token_a.publicKey, maker.publicKey, BigInt("1000000000"));"#,
+            ".signers([maker])\n            .rpc();",
+        ] {
+            assert!(bankrun.contains(expected), "missing {}", expected);
+        }
+        assert!(!bankrun.contains("confirmTransaction"));
+        assert!(!bankrun.contains("AnchorProvider.env()"));
+    }
+}
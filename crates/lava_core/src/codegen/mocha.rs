@@ -0,0 +1,906 @@
+use convert_case::{Case, Casing};
+use serde_json::{json, Value};
+
+use crate::codegen::{
+    arg, declarations, field_writers, initialize_mint, key, lookup, program_id, token_2022_mint,
+    AccountRef, ArgValue, Assertion, Extension, SetupStep, TestGenerator, TestPlan, TestStep,
+    MAX_SAFE_INTEGER,
+};
+use crate::idl::{Idl, IdlFormat};
+use crate::lava_config::{program_variable, ProgramSource};
+use crate::token::TokenProgram;
+
+/// TypeScript tests for `anchor test`, run by mocha.
+pub struct Mocha;
+
+/// TypeScript tests for `anchor test`, run by jest.
+pub struct Jest;
+
+/// TypeScript tests for `anchor test`, run by vitest.
+pub struct Vitest;
+
+impl TestGenerator for Mocha {
+    fn generate(&self, plan: &TestPlan) -> String {
+        render(plan, "", "")
+    }
+}
+
+impl TestGenerator for Jest {
+    fn generate(&self, plan: &TestPlan) -> String {
+        // Transactions against a validator easily outlast jest's 5s default.
+        render(plan, "\n\njest.setTimeout(60_000);", "")
+    }
+}
+
+impl TestGenerator for Vitest {
+    fn generate(&self, plan: &TestPlan) -> String {
+        render(
+            plan,
+            "\nimport { describe, it } from \"vitest\";",
+            "{ timeout: 60_000 }, ",
+        )
+    }
+}
+
+/// The IDL to embed for a program deployed at `address`. 0.30 clients take
+/// the program address from the IDL itself.
+pub(crate) fn embedded_idl(idl: &Idl, address: &str) -> Value {
+    let mut source = idl.source().clone();
+    if idl.format == IdlFormat::Spec {
+        source["address"] = json!(address);
+    }
+    source
+}
+
+/// The `new Program(...)` expression for `idl`, read from
+/// `<program_name>_idl` and `<program_name>_address`, with `generic` the
+/// type argument, e.g. `<AnchorEscrow>`.
+pub(crate) fn new_program(idl: &Idl, program_name: &str, generic: &str) -> String {
+    match idl.format {
+        IdlFormat::Legacy => {
+            format!("new Program{generic}({program_name}_idl, {program_name}_address, provider)")
+        }
+        IdlFormat::Spec => format!("new Program{generic}({program_name}_idl, provider)"),
+    }
+}
+
+/// The `@solana/spl-token` exports mocha imports for every plan using SPL.
+const MOCHA_SPL_IMPORTS: [&str; 8] = [
+    "ASSOCIATED_TOKEN_PROGRAM_ID",
+    "MINT_SIZE",
+    "TOKEN_PROGRAM_ID",
+    "createAssociatedTokenAccountIdempotentInstruction",
+    "createInitializeMint2Instruction",
+    "createMintToInstruction",
+    "getAssociatedTokenAddressSync",
+    "getMinimumBalanceForRentExemptMint",
+];
+
+/// Imports for Token-2022 mints and their extensions, and for handing mints
+/// over, leaving out the `@solana/spl-token` exports in `imported`.
+pub(crate) fn token_imports(plan: &TestPlan, imported: &[&str]) -> String {
+    let extensions = || {
+        plan.setup.iter().flat_map(|s| match s {
+            SetupStep::CreateMint { extensions, .. } => extensions.iter(),
+            _ => [].iter(),
+        })
+    };
+    let mut names = vec![];
+    if plan.uses_token_2022() {
+        names.extend([
+            "TOKEN_2022_PROGRAM_ID",
+            "createAssociatedTokenAccountIdempotentInstruction",
+            "createInitializeMint2Instruction",
+            "createMintToInstruction",
+        ]);
+    }
+    if plan
+        .setup
+        .iter()
+        .any(|s| matches!(s, SetupStep::SetMintAuthority { .. }))
+    {
+        names.extend(["AuthorityType", "createSetAuthorityInstruction"]);
+    }
+    let (mut metadata, mut state) = (false, false);
+    for extension in extensions() {
+        names.push(match extension {
+            Extension::TransferFee { .. } => "createInitializeTransferFeeConfigInstruction",
+            Extension::InterestBearing { .. } => "createInitializeInterestBearingMintInstruction",
+            Extension::NonTransferable => "createInitializeNonTransferableMintInstruction",
+            Extension::PermanentDelegate { .. } => "createInitializePermanentDelegateInstruction",
+            Extension::MetadataPointer { .. } => "createInitializeMetadataPointerInstruction",
+            Extension::TransferHook { .. } => "createInitializeTransferHookInstruction",
+            Extension::DefaultAccountState { .. } => {
+                state = true;
+                "createInitializeDefaultAccountStateInstruction"
+            }
+            Extension::TokenMetadata { .. } => {
+                metadata = true;
+                continue;
+            }
+        });
+    }
+    if state {
+        names.push("AccountState");
+    }
+    names.sort();
+    names.dedup();
+    names.retain(|n| !imported.contains(n));
+    let mut imports = if names.is_empty() {
+        "".to_string()
+    } else {
+        format!(
+            "\nimport {{\n    {},\n}} from \"@solana/spl-token\";",
+            names.join(",\n    ")
+        )
+    };
+    if metadata {
+        imports.push_str(
+            "\nimport { createInitializeInstruction, createUpdateFieldInstruction } from \"@solana/spl-token-metadata\";",
+        );
+    }
+    imports
+}
+
+/// The instruction handing the mint of a `SetMintAuthority` step over from
+/// the provider.
+pub(crate) fn set_mint_authority(step: &SetupStep, provider: &str) -> String {
+    let SetupStep::SetMintAuthority {
+        mint,
+        authority,
+        token_program,
+    } = step
+    else {
+        return "".to_string();
+    };
+    format!(
+        "createSetAuthorityInstruction({}.publicKey, {}, AuthorityType.MintTokens, {}{})",
+        mint,
+        provider,
+        key(authority, provider),
+        match token_program {
+            TokenProgram::Token => "",
+            TokenProgram::Token2022 => ", [], TOKEN_2022_PROGRAM_ID",
+        }
+    )
+}
+
+/// The instructions creating the ATA of a `MintTo` step and minting to it,
+/// signed by the authority the mint is created with.
+pub(crate) fn mint_to(plan: &TestPlan, step: &SetupStep, provider: &str) -> Vec<String> {
+    let SetupStep::MintTo {
+        ata,
+        mint,
+        owner,
+        amount,
+        token_program,
+    } = step
+    else {
+        return vec![];
+    };
+    let authority = plan.mint_authority(mint);
+    // spl-token takes amounts past what a JS number holds as a bigint.
+    let amount = if *amount as u128 > MAX_SAFE_INTEGER {
+        format!("BigInt(\"{}\")", amount)
+    } else {
+        amount.to_string()
+    };
+    let (signers, program_id) = match token_program {
+        TokenProgram::Token => ("", ""),
+        TokenProgram::Token2022 => (", []", program_id(token_program)),
+    };
+    vec![
+        format!(
+            "createAssociatedTokenAccountIdempotentInstruction({provider}, {ata}, {}, {mint}.publicKey{program_id})",
+            key(owner, provider)
+        ),
+        format!(
+            "createMintToInstruction({mint}.publicKey, {ata}, {}, {amount}{signers}{program_id})",
+            key(&authority, provider)
+        ),
+    ]
+}
+
+/// How a generator reads account state for assertions, as expressions of
+/// an `address: PublicKey` in scope.
+pub(crate) struct StateReads {
+    pub lamports: &'static str,
+    pub token_amount: &'static str,
+    pub exists: &'static str,
+    /// The import `token_amount` needs, unless the file already has it.
+    pub token_import: &'static str,
+}
+
+fn reads_lamports(plan: &TestPlan) -> bool {
+    plan.tests.iter().any(|t| {
+        t.asserts
+            .iter()
+            .any(|a| matches!(a, Assertion::Lamports { .. }))
+            || t.deltas.iter().any(|d| !d.tokens)
+    })
+}
+
+fn reads_tokens(plan: &TestPlan) -> bool {
+    plan.tests.iter().any(|t| {
+        t.asserts
+            .iter()
+            .any(|a| matches!(a, Assertion::TokenAmount { .. }))
+            || t.deltas.iter().any(|d| d.tokens)
+    })
+}
+
+/// Imports for the assertions and deltas of `plan`, if it has any.
+pub(crate) fn state_imports(plan: &TestPlan, reads: &StateReads) -> String {
+    if plan
+        .tests
+        .iter()
+        .all(|t| t.asserts.is_empty() && t.deltas.is_empty())
+    {
+        return "".to_string();
+    }
+    let mut imports = "\nimport * as assert from \"assert\";".to_string();
+    if reads_tokens(plan) && !reads.token_import.is_empty() {
+        imports.push('\n');
+        imports.push_str(reads.token_import);
+    }
+    imports
+}
+
+/// The helpers the assertions and deltas of `plan` read account state with.
+pub(crate) fn state_helpers(plan: &TestPlan, reads: &StateReads) -> String {
+    let asserts = || plan.tests.iter().flat_map(|t| &t.asserts);
+    let mut helpers = vec![];
+    if reads_lamports(plan) {
+        helpers.push(format!(
+            "const getLamports = async (address: PublicKey): Promise<bigint> =>\n    {};",
+            reads.lamports
+        ));
+    }
+    if reads_tokens(plan) {
+        helpers.push(format!(
+            "const getTokenAmount = async (address: PublicKey): Promise<bigint> =>\n    {};",
+            reads.token_amount
+        ));
+    }
+    if asserts().any(|a| matches!(a, Assertion::Exists { .. })) {
+        helpers.push(format!(
+            "const accountExists = async (address: PublicKey): Promise<boolean> =>\n    {};",
+            reads.exists
+        ));
+    }
+    if asserts().any(|a| matches!(a, Assertion::Data { .. })) {
+        // Compares decoded fields by their JSON form, which is how `BN`s and
+        // `PublicKey`s of equal value compare equal.
+        helpers.push(
+            "const same = (value: unknown) => JSON.parse(JSON.stringify(value));".to_string(),
+        );
+    }
+    if plan.tests.iter().any(|t| !t.deltas.is_empty()) {
+        helpers.push(
+            r#"const assertChange = (name: string, before: bigint, after: bigint, min: bigint | null, max: bigint | null) => {
+    const change = after - before;
+    assert.ok(
+        (min === null || change >= min) && (max === null || change <= max),
+        `${name} changed by ${change}, expected ${min ?? "any"} to ${max ?? "any"}`
+    );
+};"#
+            .to_string(),
+        );
+    }
+    if helpers.is_empty() {
+        "".to_string()
+    } else {
+        format!("\n{}\n", helpers.join("\n"))
+    }
+}
+
+/// The balances a test's deltas track, read into `variable`.
+fn snapshot(t: &TestStep, variable: &str) -> String {
+    if t.deltas.is_empty() {
+        return "".to_string();
+    }
+    format!(
+        "\n            const {} = [{}];",
+        variable,
+        t.deltas
+            .iter()
+            .map(|d| {
+                let address = arg(&ArgValue::PublicKey(d.account.clone()));
+                if d.tokens {
+                    // Token accounts the call creates or closes hold nothing.
+                    format!("await getTokenAmount({}).catch(() => BigInt(0))", address)
+                } else {
+                    format!("await getLamports({})", address)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+/// Checks each delta of a test against the balances snapshotted around the
+/// call.
+fn deltas(t: &TestStep) -> String {
+    let bound = |b: Option<i128>| match b {
+        Some(b) => format!("BigInt(\"{}\")", b),
+        None => "null".to_string(),
+    };
+    t.deltas
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            format!(
+                "\n            assertChange(\"{} {}\", before[{i}], after[{i}], {}, {});",
+                d.account.identifier(),
+                if d.tokens { "tokens" } else { "lamports" },
+                bound(d.min),
+                bound(d.max)
+            )
+        })
+        .collect()
+}
+
+/// The checks a test makes on account state once its call is done.
+fn assertions(t: &TestStep) -> String {
+    t.asserts
+        .iter()
+        .map(|assertion| match assertion {
+            Assertion::Lamports { account, lamports } => format!(
+                "\n            assert.strictEqual(await getLamports({}), BigInt(\"{}\"));",
+                arg(&ArgValue::PublicKey(account.clone())),
+                lamports
+            ),
+            Assertion::TokenAmount { account, amount } => format!(
+                "\n            assert.strictEqual(await getTokenAmount({}), BigInt(\"{}\"));",
+                arg(&ArgValue::PublicKey(account.clone())),
+                amount
+            ),
+            Assertion::Exists { account, exists } => format!(
+                "\n            assert.strictEqual(await accountExists({}), {});",
+                arg(&ArgValue::PublicKey(account.clone())),
+                exists
+            ),
+            Assertion::Data {
+                account,
+                program,
+                account_type,
+                fields,
+            } => format!(
+                r#"
+            {{
+                const data = await {}.account.{}.fetch({});{}
+            }}"#,
+                program_variable(program),
+                account_type.to_case(Case::Camel),
+                arg(&ArgValue::PublicKey(account.clone())),
+                fields
+                    .iter()
+                    .map(|(field, value)| format!(
+                        "\n                assert.deepStrictEqual(same(data.{}), same({}));",
+                        field.to_case(Case::Camel),
+                        arg(value)
+                    ))
+                    .collect::<String>()
+            ),
+        })
+        .collect()
+}
+
+/// An `it` block per test, each sending its instruction with `send`, e.g.
+/// `.rpc()`.
+pub(crate) fn tests(plan: &TestPlan, send: &str) -> String {
+    plan.tests
+        .iter()
+        .map(|t| {
+            // 0.30 clients camelCase IDL names and resolve PDAs and fixed
+            // addresses themselves, so every account goes through
+            // `accountsPartial`.
+            let spec = plan
+                .program(&t.program)
+                .is_some_and(|p| p.idl.format == IdlFormat::Spec);
+            let ts_name = |name: &str| {
+                if spec {
+                    name.to_case(Case::Camel)
+                } else {
+                    name.to_string()
+                }
+            };
+            // The provider signs every transaction it sends, so only
+            // keypairs the test holds are passed.
+            let mut signers: Vec<String> = vec![];
+            for meta in t.accounts.iter().filter(|a| a.signer) {
+                if let Some(AccountRef::Keypair(id)) = &meta.account {
+                    if !signers.contains(id) {
+                        signers.push(id.clone());
+                    }
+                }
+            }
+            let signers_part = if !signers.is_empty() {
+                format!("\n.signers([{}])", signers.join(", "))
+            } else {
+                "".to_owned()
+            };
+            let account_display = t
+                .accounts
+                .iter()
+                .filter_map(|a| {
+                    let account = a.account.as_ref()?;
+                    Some(match account {
+                        AccountRef::Literal(_) => {
+                            format!("{}: {}", ts_name(&a.name), key(account, ""))
+                        }
+                        _ => format!(
+                            r#"{}: accountsPublicKeys["{}"]"#,
+                            ts_name(&a.name),
+                            lookup(account)
+                        ),
+                    })
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+            let arguments = t
+                .args
+                .iter()
+                .map(|a| a.constant.clone().unwrap_or_else(|| arg(&a.value)))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let derive = plan
+                .accounts
+                .iter()
+                .filter(|a| a.is_deferred() && t.uses(&a.identifier))
+                .map(|a| {
+                    format!(
+                        "accountsPublicKeys[\"{id}\"] = await derive_{id}();\n            ",
+                        id = a.identifier
+                    )
+                })
+                .collect::<String>();
+            let instructions = ts_name(&t.instruction);
+            let accounts_method = if spec { "accountsPartial" } else { "accounts" };
+            let name = &t.name;
+            let program = program_variable(&t.program);
+            let call = format!(
+                r#"{program}.methods
+            .{instructions}({arguments})
+            .{accounts_method}({{ ...accounts }}){signers_part}"#
+            );
+            let call = match &t.expect {
+                Some(expected) => format!(
+                    r#"await expectAnchorError(
+            {call}
+            .rpc(), {}, "{}")"#,
+                    expected.code,
+                    expected.name.as_deref().unwrap_or("custom error")
+                ),
+                None => format!(
+                    r#"await {call}
+            {send}"#
+                ),
+            };
+            let before = snapshot(t, "before");
+            let after = snapshot(t, "after");
+            let deltas = deltas(t);
+            let assertions = assertions(t);
+            format!(
+                r#"it("{name}", async() => {{
+            {derive}const accounts = {{{account_display}}}{before}
+            {call};{after}{deltas}{assertions}
+        }});"#
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// `expectAnchorError`, which checks that a call fails with a given Anchor
+/// error, for plans with tests that expect one.
+pub(crate) fn expect_anchor_error(plan: &TestPlan) -> &'static str {
+    if plan.tests.iter().all(|t| t.expect.is_none()) {
+        return "";
+    }
+    r#"
+const expectAnchorError = async (call: Promise<unknown>, code: number, name: string) => {
+    const error = await call.then(() => undefined, (e) => e);
+    const anchorError =
+        error instanceof anchor.AnchorError ? error : anchor.AnchorError.parse(error?.logs ?? []);
+    if (anchorError?.error.errorCode.number !== code) {
+        throw new Error(`expected ${name} (${code}), got ${error ?? "success"}`);
+    }
+};
+"#
+}
+
+/// The mocha flavoured test, with `runner` appended to the imports and
+/// `describe_options` passed to the top-level `describe`.
+fn render(plan: &TestPlan, runner: &str, describe_options: &str) -> String {
+    let mut import_program_types = "".to_string();
+    let mut declare_programs = "".to_string();
+
+    for program in &plan.programs {
+        let idl = &program.idl;
+        let program_name = idl.name.to_case(Case::Snake);
+        let program_type = idl.name.to_case(Case::Pascal);
+        let program_definition = match &program.source {
+            ProgramSource::Workspace => {
+                import_program_types.push_str(&format!(
+                    "\nimport {{ {} }} from \"../target/types/{}\";",
+                    program_type, program_name
+                ));
+                format!(
+                    r#"const {} = anchor.workspace.{} as Program<{}>;"#,
+                    program_variable(&idl.name),
+                    program_type,
+                    program_type
+                )
+            }
+            // Not built by this workspace, so there are no generated types
+            // or IDL file to import; embed the IDL instead.
+            ProgramSource::Address(address) => format!(
+                r#"const {program_name}_address = new PublicKey("{address}");
+const {program_name}_idl = {} as anchor.Idl;
+const {} = {};"#,
+                serde_json::to_string_pretty(&embedded_idl(idl, address)).unwrap_or_default(),
+                program_variable(&idl.name),
+                new_program(idl, &program_name, ""),
+            ),
+        };
+        declare_programs.push('\n');
+        declare_programs.push_str(&program_definition);
+    }
+    import_program_types.push_str(runner);
+
+    let (accounts_declarations, accounts) = declarations(plan, "provider.publicKey", |program| {
+        format!("{}.programId", program_variable(program))
+    });
+
+    // Keep in step with `MOCHA_SPL_IMPORTS`.
+    let import_spl = if plan.uses_spl() {
+        r#"import {
+                ASSOCIATED_TOKEN_PROGRAM_ID,
+                MINT_SIZE,
+                TOKEN_PROGRAM_ID,
+                createAssociatedTokenAccountIdempotentInstruction,
+                createInitializeMint2Instruction,
+                createMintToInstruction,
+                getAssociatedTokenAddressSync,
+                getMinimumBalanceForRentExemptMint,
+                } from "@solana/spl-token";"#
+    } else {
+        ""
+    };
+
+    let accounts_part = format!(
+        r#"{}
+        const accountsPublicKeys = {{{}
+    }}"#,
+        accounts_declarations.join("\n"),
+        accounts.join(",\n"),
+    );
+
+    let user_defined_tests = tests(
+        plan,
+        r#".rpc()
+            .then(confirm)
+            .then(log)"#,
+    );
+
+    let mut setup_wallets = vec![];
+    let mut setup_mints = vec![];
+    let mut mint_instructions = vec![];
+    let mut funded_wallets = vec![];
+    let mut mints = vec![];
+    let mut authorities = vec![];
+    for step in &plan.setup {
+        match step {
+            SetupStep::FundWallet { wallet, sol } => {
+                setup_wallets.push(format!(
+                    r#"{{
+        pubkey: {}.publicKey,
+        lamports: {} * LAMPORTS_PER_SOL,
+      }}"#,
+                    wallet, sol
+                ));
+                funded_wallets.push(wallet.clone());
+            }
+            SetupStep::CreateMint {
+                mint,
+                mint_authority,
+                token_program,
+                extensions,
+                ..
+            } => {
+                if token_program.is_token() {
+                    setup_mints.push(format!(
+                        "SystemProgram.createAccount({{
+        fromPubkey: provider.publicKey,
+        newAccountPubkey: {}.publicKey,
+        lamports,
+        space: MINT_SIZE,
+        programId: TOKEN_PROGRAM_ID,
+      }})",
+                        mint
+                    ));
+                    setup_mints.push(initialize_mint(step, "provider.publicKey"));
+                } else {
+                    setup_mints.extend(token_2022_mint(step, "provider.publicKey", |len| {
+                        format!(
+                            "await connection.getMinimumBalanceForRentExemption({})",
+                            len
+                        )
+                    }));
+                }
+                mints.push(mint.clone());
+                // Mint-tos and token metadata are signed by the authority.
+                if let AccountRef::Keypair(authority) = mint_authority {
+                    authorities.push(authority.clone());
+                }
+                // As are the additional token metadata fields, by the update
+                // authority.
+                authorities.extend(field_writers(extensions));
+            }
+            SetupStep::MintTo { .. } => {
+                mint_instructions.push(mint_to(plan, step, "provider.publicKey").join(",\n"))
+            }
+            SetupStep::SetMintAuthority { .. } => {
+                mint_instructions.push(set_mint_authority(step, "provider.publicKey"))
+            }
+        }
+    }
+    let setup_wallets = setup_wallets.join(",\n");
+    let setup = setup_mints
+        .into_iter()
+        .chain(mint_instructions)
+        .collect::<Vec<String>>()
+        .join(",\n");
+
+    let rent_exception = if !import_spl.is_empty() {
+        "let lamports = await getMinimumBalanceForRentExemptMint(connection);"
+    } else {
+        ""
+    };
+
+    let name = &plan.name;
+    let expect_anchor_error = expect_anchor_error(plan);
+    let reads = StateReads {
+        lamports: "BigInt(await connection.getBalance(address))",
+        // Token-2022 accounts are only found under their own program.
+        token_amount: if plan.uses_token_2022() {
+            "(await getAccount(connection, address, undefined, (await connection.getAccountInfo(address))!.owner)).amount"
+        } else {
+            "(await getAccount(connection, address)).amount"
+        },
+        exists: "(await connection.getAccountInfo(address)) !== null",
+        token_import: "import { getAccount } from \"@solana/spl-token\";",
+    };
+    let state_imports = state_imports(plan, &reads);
+    let state_helpers = state_helpers(plan, &reads);
+    let token_imports = token_imports(plan, &MOCHA_SPL_IMPORTS);
+    let mut signers = mints;
+    for signer in funded_wallets.into_iter().chain(authorities) {
+        if !signers.contains(&signer) {
+            signers.push(signer);
+        }
+    }
+    let instruction = signers.join(", ");
+
+    format!(
+        r#"
+import * as anchor from "@coral-xyz/anchor";
+import {{ Program, BN }} from "@coral-xyz/anchor";
+import {{
+    Keypair,
+    LAMPORTS_PER_SOL,
+    PublicKey,
+    SystemProgram,
+    Transaction,
+    }} from "@solana/web3.js";
+{import_spl}{token_imports}{state_imports}
+{import_program_types}
+
+describe("{name}", {describe_options}() => {{
+    anchor.setProvider(anchor.AnchorProvider.env());
+
+    const provider = anchor.getProvider();
+
+    const connection = provider.connection;
+
+{declare_programs}
+
+    const confirm = async (signature: string): Promise<string> => {{
+        const block = await connection.getLatestBlockhash();
+        await connection.confirmTransaction({{
+            signature,
+            ...block,
+        }});
+    return signature;
+}};
+
+const log = async (signature: string): Promise<string> => {{
+    console.log(
+    `Your transaction signature: https://explorer.solana.com/transaction/${{signature}}?cluster=custom&customUrl=${{connection.rpcEndpoint}}`
+    );
+    return signature;
+}};
+{expect_anchor_error}{state_helpers}
+// Accounts
+{accounts_part}
+
+it("setup", async() => {{
+    {rent_exception}
+    let tx = new Transaction();
+    [ {setup_wallets} ].forEach(account => {{
+        tx.add(SystemProgram.createAccount({{
+            fromPubkey: provider.publicKey,
+            newAccountPubkey: account.pubkey,
+            lamports: account.lamports,
+            space: 0,
+            programId: SystemProgram.programId,
+        }}));
+    }});
+    [ {setup} ].forEach(instruction => {{
+        tx.add(instruction);
+    }});
+    await provider.sendAndConfirm(tx, [{instruction}]).then(log);
+}})
+
+{user_defined_tests}
+}})"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::{
+        token_2022_mint, Bankrun, Jest, Mocha, RustProgramTest, SetupStep, Vitest,
+    };
+    use crate::fixtures;
+
+    #[test]
+    fn renders_other_runners() {
+        let config = fixtures::config(&fixtures::escrow_make());
+        let jest = config.try_generate(&Jest).unwrap();
+        // Every key tests look builtins up by is declared.
+        assert!(jest.contains("tokenProgram: accountsPublicKeys[\"token_program\"]"));
+        assert!(jest.contains("token_program: TOKEN_PROGRAM_ID"));
+        assert!(jest.contains("jest.setTimeout(60_000);\n\ndescribe(\"Escrow\", () => {"));
+        let vitest = config.try_generate(&Vitest).unwrap();
+        assert!(vitest.contains("import { describe, it } from \"vitest\";"));
+        assert!(vitest.contains("describe(\"Escrow\", { timeout: 60_000 }, () => {"));
+    }
+
+    #[test]
+    fn targets_anchor_030_clients() {
+        let config = fixtures::config(&fixtures::escrow_anchor_030());
+
+        let mocha = config.try_generate(&Mocha).unwrap();
+        for expected in [
+            "mintA: accountsPublicKeys[\"token_a\"]",
+            "makerAtaA: accountsPublicKeys[\"maker_ata_a\"]",
+            ".make(make_seed, new BN(1000000), new BN(1000000))",
+            ".accountsPartial({ ...accounts })",
+            // The escrow seeds come from the IDL's pda metadata.
+            "const escrow = PublicKey.findProgramAddressSync([Buffer.from(\"657363726f77\", \"hex\"), maker.publicKey.toBuffer(), new BN(make_seed).toBuffer(\"le\", 8)]",
+        ] {
+            assert!(mocha.contains(expected), "missing {}", expected);
+        }
+
+        let bankrun = config.try_generate(&Bankrun).unwrap();
+        assert!(bankrun.contains("new PublicKey(anchor_escrow_idl.address)"));
+
+        let rust = config.try_generate(&RustProgramTest).unwrap();
+        assert!(rust.contains("vec![138u8, 227u8, 232u8, 77u8, 223u8, 166u8, 96u8, 197u8],"));
+        assert!(!rust.contains("fn sighash"));
+    }
+
+    #[test]
+    fn escapes_string_seeds_and_wide_amounts() {
+        let mut json = fixtures::escrow_make();
+        json["accounts"][10]["seeds"][0]["value"] = "say \"hi\"\n".into();
+        json["accounts"][5]["amount"] = (1u64 << 60).into();
+        let mocha = fixtures::config(&json).try_generate(&Mocha).unwrap();
+        assert!(
+            mocha.contains(r#"[Buffer.from("say \"hi\"\n", "utf-8"), maker.publicKey.toBuffer()"#)
+        );
+        assert!(mocha.contains(
+            r#"createMintToInstruction(token_a.publicKey, maker_ata_a, provider.publicKey, BigInt("1152921504606846976"))"#
+        ));
+        assert!(mocha.contains(
+            "createMintToInstruction(token_b.publicKey, taker_ata_b, provider.publicKey, 1000000000)"
+        ));
+    }
+
+    #[test]
+    fn signs_with_mapped_keypairs() {
+        let mut json = fixtures::escrow();
+        json["accounts"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::json!({ "name": "Alice", "kind": "wallet", "balance": 10 }));
+        json["tests"][0]["accounts"]["maker"] = "provider".into();
+        json["tests"][1]["accounts"]["taker"] = "Alice".into();
+        let mocha = fixtures::config(&json).try_generate(&Mocha).unwrap();
+        // The provider signs Make by sending it.
+        assert_eq!(mocha.matches(".signers(").count(), 1);
+        assert!(mocha.contains(".signers([alice])"));
+    }
+
+    #[test]
+    fn signs_metadata_fields_with_the_update_authority() {
+        let mut json = fixtures::escrow_make();
+        json["accounts"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::json!({ "name": "Curator", "kind": "wallet", "balance": 0 }));
+        json["accounts"][2]["tokenProgram"] = "token2022".into();
+        json["accounts"][2]["extensions"] = serde_json::json!([
+            { "kind": "metadataPointer" },
+            { "kind": "tokenMetadata", "updateAuthority": "Curator", "additional": { "color": "red" } },
+        ]);
+        let mocha = fixtures::config(&json).try_generate(&Mocha).unwrap();
+        assert!(mocha.contains(
+            "await provider.sendAndConfirm(tx, [token_a, token_b, maker, taker, curator])"
+        ));
+    }
+
+    #[test]
+    fn orders_token_2022_mint_instructions() {
+        let mut json = fixtures::escrow_make();
+        json["accounts"][2]["tokenProgram"] = "token2022".into();
+        json["accounts"][2]["mint_authority"] = "Maker".into();
+        json["accounts"][2]["extensions"] = serde_json::json!([
+            { "kind": "tokenMetadata", "uri": "https://lava.dev/a.json", "additional": { "color": "red" } },
+            { "kind": "interestBearing", "rate": 300 },
+            { "kind": "metadataPointer" },
+            { "kind": "transferFee", "feeBasisPoints": 50, "maxFee": 5000 },
+        ]);
+        json["tests"][0]["accounts"]["tokenProgram"] = "token2022Program".into();
+        let plan = fixtures::config(&json).plan().unwrap();
+
+        // Wallets are funded first, then every mint is created before any
+        // of them is minted to.
+        let steps: Vec<String> = plan
+            .setup
+            .iter()
+            .map(|s| match s {
+                SetupStep::FundWallet { wallet, .. } => format!("fund {}", wallet),
+                SetupStep::CreateMint { mint, .. } => format!("create {}", mint),
+                SetupStep::MintTo { ata, .. } => format!("mint to {}", ata),
+                SetupStep::SetMintAuthority { mint, .. } => format!("hand over {}", mint),
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                "fund maker",
+                "fund taker",
+                "create token_a",
+                "create token_b",
+                "mint to maker_ata_a",
+                "mint to taker_ata_b",
+            ]
+        );
+
+        let token_a = plan
+            .setup
+            .iter()
+            .find(|s| matches!(s, SetupStep::CreateMint { mint, .. } if mint == "token_a"))
+            .unwrap();
+        let calls: Vec<String> =
+            token_2022_mint(token_a, "provider.publicKey", |len| len.to_string())
+                .iter()
+                .map(|i| i[..i.find('(').unwrap()].to_string())
+                .collect();
+        assert_eq!(
+            calls,
+            [
+                "SystemProgram.createAccount",
+                "createInitializeTransferFeeConfigInstruction",
+                "createInitializeInterestBearingMintInstruction",
+                "createInitializeMetadataPointerInstruction",
+                "createInitializeMint2Instruction",
+                "createInitializeInstruction",
+                "createUpdateFieldInstruction",
+            ]
+        );
+    }
+}
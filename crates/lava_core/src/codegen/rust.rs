@@ -0,0 +1,1109 @@
+use convert_case::{Case, Casing};
+use soda_sol::structs::InstructionType;
+
+use crate::builtins::builtin;
+use crate::codegen::{
+    Account, AccountKind, AccountRef, ArgValue, Assertion, Delta, Extension, Seed, SetupStep,
+    TestGenerator, TestPlan, TestStep,
+};
+use crate::error::{LavaError, LavaErrorKind};
+use crate::idl::IdlFormat;
+use crate::lava_config::{program_variable, ProgramSource};
+use crate::token::{funded_mint_len, mint_len, TokenProgram};
+
+/// A `#[tokio::test]` for `solana-program-test`, to be placed in the `tests`
+/// directory of the program crate. Optional accounts left unmapped are passed
+/// as the program id, Anchor's `None`; any other account a test leaves
+/// unresolved is reported, since the instruction can't be built without it.
+pub struct RustProgramTest;
+
+/// `items` as a Rust tuple, which needs a trailing comma for one element.
+fn tuple(items: &[String]) -> String {
+    if items.len() == 1 {
+        format!("({},)", items[0])
+    } else {
+        format!("({})", items.join(", "))
+    }
+}
+
+/// The Rust type an IDL type deserializes to in the program crate `krate`,
+/// used where a literal alone doesn't pin it down, like `None` or an empty
+/// `Vec`.
+///
+/// Defined types are expected at the root of the program crate, which is
+/// where Anchor programs conventionally re-export their state.
+fn rust_type(krate: &str, kind: &InstructionType) -> String {
+    match kind {
+        InstructionType::U8 => "u8".to_string(),
+        InstructionType::U16 => "u16".to_string(),
+        InstructionType::U32 => "u32".to_string(),
+        InstructionType::U64 => "u64".to_string(),
+        InstructionType::U128 => "u128".to_string(),
+        InstructionType::I8 => "i8".to_string(),
+        InstructionType::I16 => "i16".to_string(),
+        InstructionType::I32 => "i32".to_string(),
+        InstructionType::I64 => "i64".to_string(),
+        InstructionType::I128 => "i128".to_string(),
+        InstructionType::Bool => "bool".to_string(),
+        InstructionType::String => "String".to_string(),
+        InstructionType::PublicKey => "Pubkey".to_string(),
+        InstructionType::Bytes => "Vec<u8>".to_string(),
+        InstructionType::Option(inner) => format!("Option<{}>", rust_type(krate, inner)),
+        InstructionType::Vec(inner) => format!("Vec<{}>", rust_type(krate, inner)),
+        InstructionType::Array(inner, len) => format!("[{}; {}]", rust_type(krate, inner), len),
+        InstructionType::Tuple(kinds) => tuple(
+            &kinds
+                .iter()
+                .map(|k| rust_type(krate, k))
+                .collect::<Vec<String>>(),
+        ),
+        InstructionType::Defined(name) => format!("{}::{}", krate, name),
+        InstructionType::HashSet(inner) => {
+            format!("std::collections::HashSet<{}>", rust_type(krate, inner))
+        }
+        InstructionType::BTreeSet(inner) => {
+            format!("std::collections::BTreeSet<{}>", rust_type(krate, inner))
+        }
+        InstructionType::HashMap(key, val) => format!(
+            "std::collections::HashMap<{}, {}>",
+            rust_type(krate, key),
+            rust_type(krate, val)
+        ),
+        InstructionType::BTreeMap(key, val) => format!(
+            "std::collections::BTreeMap<{}, {}>",
+            rust_type(krate, key),
+            rust_type(krate, val)
+        ),
+    }
+}
+
+/// The `Pubkey` expression for `account`.
+fn key(account: &AccountRef) -> String {
+    match account {
+        AccountRef::Keypair(id) => format!("{}.pubkey()", id),
+        AccountRef::Address(id) => id.clone(),
+        AccountRef::Program(name) => program_variable(name),
+        AccountRef::Provider => "payer.pubkey()".to_string(),
+        AccountRef::Builtin(name) => match name.as_str() {
+            "systemProgram" => "system_program::ID".to_string(),
+            "tokenProgram" => "spl_token::ID".to_string(),
+            "token2022Program" => "spl_token_2022::ID".to_string(),
+            "associatedTokenProgram" => "spl_associated_token_account::ID".to_string(),
+            "computeBudgetProgram" => "solana_sdk::compute_budget::ID".to_string(),
+            "rent" => "solana_sdk::sysvar::rent::ID".to_string(),
+            "clock" => "solana_sdk::sysvar::clock::ID".to_string(),
+            "instructions" => "solana_sdk::sysvar::instructions::ID".to_string(),
+            "slotHashes" => "solana_sdk::sysvar::slot_hashes::ID".to_string(),
+            _ => format!(
+                "Pubkey::from_str(\"{}\").unwrap()",
+                builtin(name).map_or(name.as_str(), |b| b.address)
+            ),
+        },
+        AccountRef::Literal(address) => format!("Pubkey::from_str(\"{}\").unwrap()", address),
+    }
+}
+
+/// The keypair that signs for `account`, if the test holds one.
+fn signer(account: &AccountRef) -> Option<String> {
+    match account {
+        AccountRef::Keypair(id) => Some(format!("&{}", id)),
+        AccountRef::Provider => Some("&payer".to_string()),
+        _ => None,
+    }
+}
+
+/// The seed as a `&[u8]` expression for `Pubkey::find_program_address`.
+fn seed(seed: &Seed) -> String {
+    match seed {
+        Seed::Account(account) => format!("{}.as_ref()", key(account)),
+        Seed::String(s) => format!("{:?}.as_bytes()", s),
+        Seed::Integer {
+            value,
+            signed,
+            bytes,
+        } => {
+            let int = format!(
+                "{}{}{}",
+                value,
+                if *signed { "i" } else { "u" },
+                *bytes as u32 * 8
+            );
+            // Unary minus binds looser than the method call.
+            if value.starts_with('-') {
+                format!("&({}).to_le_bytes()", int)
+            } else {
+                format!("&{}.to_le_bytes()", int)
+            }
+        }
+        Seed::Bytes(bytes) => format!(
+            "&[{}][..]",
+            bytes
+                .iter()
+                .map(|b| format!("{}u8", b))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Seed::Arg { constant, kind, .. } => seed_bytes(constant, kind),
+        Seed::Field {
+            account,
+            field,
+            kind,
+            ..
+        } => seed_bytes(
+            &format!(
+                "{}_data.{}",
+                account.identifier(),
+                field.to_case(Case::Snake)
+            ),
+            kind,
+        ),
+    }
+}
+
+/// The seed bytes of `value`, an expression of IDL type `kind`.
+fn seed_bytes(value: &str, kind: &InstructionType) -> String {
+    match kind {
+        InstructionType::Bool => format!("&[{} as u8][..]", value),
+        InstructionType::String => format!("{}.as_bytes()", value),
+        InstructionType::PublicKey => format!("{}.as_ref()", value),
+        InstructionType::Bytes | InstructionType::Array(..) => format!("&{}[..]", value),
+        _ => format!("&{}.to_le_bytes()", value),
+    }
+}
+
+/// Derives the PDA `account` from its seeds, reading the program accounts
+/// its field seeds come from first.
+fn derive(account: &Account) -> String {
+    let AccountKind::Pda { program, seeds } = &account.kind else {
+        return "".to_string();
+    };
+    let mut reads: Vec<String> = vec![];
+    for s in seeds {
+        if let Seed::Field {
+            account,
+            program,
+            account_type,
+            ..
+        } = s
+        {
+            let read = format!(
+                "\n            let {}_data = {}::{}::try_deserialize(&mut banks_client.get_account({}).await.unwrap().unwrap().data.as_slice()).unwrap();",
+                account.identifier(),
+                program.to_case(Case::Snake),
+                account_type,
+                key(account)
+            );
+            if !reads.contains(&read) {
+                reads.push(read);
+            }
+        }
+    }
+    let find = format!(
+        "Pubkey::find_program_address(&[{}], &{})",
+        seeds.iter().map(seed).collect::<Vec<String>>().join(", "),
+        program_variable(program)
+    );
+    if reads.is_empty() {
+        format!("let ({}, _) = {};", account.identifier, find)
+    } else {
+        format!(
+            "let ({}, _) = {{{}\n            {}\n        }};",
+            account.identifier,
+            reads.concat(),
+            find
+        )
+    }
+}
+
+/// Renders an arg as a Rust expression of the type the program crate
+/// `krate` deserializes it into.
+fn arg(krate: &str, value: &ArgValue) -> String {
+    let list = |items: &[ArgValue]| {
+        items
+            .iter()
+            .map(|v| arg(krate, v))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    match value {
+        ArgValue::Integer {
+            value,
+            signed,
+            bits,
+        } => format!("{}{}{}", value, if *signed { "i" } else { "u" }, bits),
+        ArgValue::Bool(b) => b.to_string(),
+        ArgValue::String(s) => format!("String::from({:?})", s),
+        ArgValue::PublicKey(account) => key(account),
+        ArgValue::Bytes(bytes) => format!(
+            "vec![{}]",
+            bytes
+                .iter()
+                .map(|b| format!("{}u8", b))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        ArgValue::Option { kind, value: None } => format!("None::<{}>", rust_type(krate, kind)),
+        ArgValue::Option {
+            value: Some(value), ..
+        } => format!("Some({})", arg(krate, value)),
+        ArgValue::Array { kind, items } if items.is_empty() => {
+            format!("[] as [{}; 0]", rust_type(krate, kind))
+        }
+        ArgValue::Array { items, .. } => format!("[{}]", list(items)),
+        ArgValue::Vec { kind, items } if items.is_empty() => {
+            format!("Vec::<{}>::new()", rust_type(krate, kind))
+        }
+        ArgValue::Vec { items, .. } => format!("vec![{}]", list(items)),
+        ArgValue::Set {
+            kind,
+            items,
+            ordered,
+        } => {
+            let set = if *ordered {
+                InstructionType::BTreeSet(Box::new(kind.clone()))
+            } else {
+                InstructionType::HashSet(Box::new(kind.clone()))
+            };
+            format!("{}::from([{}])", rust_type(krate, &set), list(items))
+        }
+        ArgValue::Tuple(items) => {
+            tuple(&items.iter().map(|v| arg(krate, v)).collect::<Vec<String>>())
+        }
+        ArgValue::Map {
+            key,
+            value,
+            entries,
+            ordered,
+        } => {
+            let (key, value) = (Box::new(key.clone()), Box::new(value.clone()));
+            let map = if *ordered {
+                InstructionType::BTreeMap(key, value)
+            } else {
+                InstructionType::HashMap(key, value)
+            };
+            format!(
+                "{}::from([{}])",
+                rust_type(krate, &map),
+                entries
+                    .iter()
+                    .map(|(k, v)| format!("({}, {})", arg(krate, k), arg(krate, v)))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+        }
+        ArgValue::Struct { name, fields } => format!(
+            "{}::{} {{ {} }}",
+            krate,
+            name,
+            fields
+                .iter()
+                .map(|(field, v)| format!("{}: {}", field.to_case(Case::Snake), arg(krate, v)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        ArgValue::Enum { name, variant } => {
+            format!("{}::{}::{}", krate, name, variant.to_case(Case::Pascal))
+        }
+    }
+}
+
+/// The amount held by the token account whose data is `data`. Token-2022
+/// accounts may carry extensions past the base account, so plans using it
+/// read every account with its unpacker, which takes both kinds.
+fn token_amount(data: &str, token_2022: bool) -> String {
+    if token_2022 {
+        format!(
+            "spl_token_2022::extension::StateWithExtensions::<spl_token_2022::state::Account>::unpack(&{}).unwrap().base.amount",
+            data
+        )
+    } else {
+        format!(
+            "spl_token::state::Account::unpack(&{}).unwrap().amount",
+            data
+        )
+    }
+}
+
+/// The balances a test's deltas track, read into `variable`.
+fn snapshot(t: &TestStep, variable: &str, token_2022: bool) -> String {
+    let read = |d: &Delta| {
+        if d.tokens {
+            format!(
+                "banks_client.get_account({}).await.unwrap().map_or(0, |a| {}) as i128",
+                key(&d.account),
+                token_amount("a.data", token_2022)
+            )
+        } else {
+            format!(
+                "banks_client.get_balance({}).await.unwrap() as i128",
+                key(&d.account)
+            )
+        }
+    };
+    format!(
+        "let {}: [i128; {}] = [\n            {},\n        ];",
+        variable,
+        t.deltas.len(),
+        t.deltas
+            .iter()
+            .map(read)
+            .collect::<Vec<String>>()
+            .join(",\n            ")
+    )
+}
+
+/// Checks each delta of a test against the balances snapshotted around the
+/// call.
+fn deltas(t: &TestStep) -> String {
+    t.deltas
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let label = format!(
+                "{} {}",
+                d.account.identifier(),
+                if d.tokens { "tokens" } else { "lamports" }
+            );
+            let range = match (d.min, d.max) {
+                (Some(min), Some(max)) if min == max => {
+                    return format!(
+                        "\n        assert_eq!(after[{i}] - before[{i}], {}, \"{}\");",
+                        min, label
+                    )
+                }
+                (Some(min), Some(max)) => format!("{}..={}", min, max),
+                (Some(min), None) => format!("{}..", min),
+                (None, Some(max)) => format!("..={}", max),
+                (None, None) => "..".to_string(),
+            };
+            format!(
+                r#"
+        assert!(
+            ({range}).contains(&(after[{i}] - before[{i}])),
+            "{label} changed by {{}}",
+            after[{i}] - before[{i}]
+        );"#
+            )
+        })
+        .collect()
+}
+
+/// The checks a test makes on account state once its call is done.
+fn assertions(t: &TestStep, token_2022: bool) -> String {
+    t.asserts
+        .iter()
+        .map(|assertion| match assertion {
+            Assertion::Lamports { account, lamports } => format!(
+                "\n        assert_eq!(banks_client.get_balance({}).await.unwrap(), {});",
+                key(account),
+                lamports
+            ),
+            Assertion::TokenAmount { account, amount } => format!(
+                r#"
+        {{
+            let account = banks_client.get_account({}).await.unwrap().unwrap();
+            assert_eq!({}, {});
+        }}"#,
+                key(account),
+                token_amount("account.data", token_2022),
+                amount
+            ),
+            Assertion::Exists { account, exists } => format!(
+                "\n        assert_eq!(banks_client.get_account({}).await.unwrap().is_some(), {});",
+                key(account),
+                exists
+            ),
+            Assertion::Data {
+                account,
+                program,
+                account_type,
+                fields,
+            } => {
+                let krate = program.to_case(Case::Snake);
+                format!(
+                    r#"
+        {{
+            let account = banks_client.get_account({}).await.unwrap().unwrap();
+            let data = {}::{}::try_deserialize(&mut account.data.as_slice()).unwrap();{}
+        }}"#,
+                    key(account),
+                    krate,
+                    account_type,
+                    fields
+                        .iter()
+                        .map(|(field, value)| format!(
+                            "\n            assert_eq!(data.{}, {});",
+                            field.to_case(Case::Snake),
+                            arg(&krate, value)
+                        ))
+                        .collect::<String>()
+                )
+            }
+        })
+        .collect()
+}
+
+/// Instructions that fund wallets, create each mint once and fill the ATAs
+/// that start with a balance, with the keypairs that must sign them.
+fn setup(plan: &TestPlan) -> (Vec<String>, Vec<String>) {
+    let mut instructions = vec![];
+    let mut signers = vec!["&payer".to_string()];
+    let mut sign = |signer: Option<String>| {
+        if let Some(signer) = signer.filter(|s| !signers.contains(s)) {
+            signers.push(signer);
+        }
+    };
+
+    for step in &plan.setup {
+        match step {
+            SetupStep::FundWallet { wallet, sol } => instructions.push(format!(
+                "system_instruction::transfer(&payer.pubkey(), &{}.pubkey(), {} * LAMPORTS_PER_SOL)",
+                wallet, sol
+            )),
+            SetupStep::CreateMint {
+                mint,
+                decimals,
+                mint_authority,
+                freeze_authority,
+                token_program: TokenProgram::Token2022,
+                extensions,
+            } => {
+                let address = format!("&{}.pubkey()", mint);
+                let id = "&spl_token_2022::ID";
+                let some = |account: &AccountRef| format!("Some({})", key(account));
+                instructions.push(format!(
+                    "system_instruction::create_account(&payer.pubkey(), {address}, rent.minimum_balance({}), {} as u64, {id})",
+                    funded_mint_len(extensions),
+                    mint_len(extensions)
+                ));
+                let mut metadata = vec![];
+                for extension in extensions {
+                    let extensions = "spl_token_2022::extension";
+                    instructions.push(match extension {
+                        Extension::TransferFee {
+                            authority,
+                            withdraw_authority,
+                            basis_points,
+                            max_fee,
+                        } => format!(
+                            "{extensions}::transfer_fee::instruction::initialize_transfer_fee_config({id}, {address}, Some(&{}), Some(&{}), {basis_points}, {max_fee}).unwrap()",
+                            key(authority),
+                            key(withdraw_authority)
+                        ),
+                        Extension::InterestBearing { authority, rate } => format!(
+                            "{extensions}::interest_bearing_mint::instruction::initialize({id}, {address}, {}, {rate}).unwrap()",
+                            some(authority)
+                        ),
+                        Extension::NonTransferable => format!(
+                            "spl_token_2022::instruction::initialize_non_transferable_mint({id}, {address}).unwrap()"
+                        ),
+                        Extension::PermanentDelegate { delegate } => format!(
+                            "spl_token_2022::instruction::initialize_permanent_delegate({id}, {address}, &{}).unwrap()",
+                            key(delegate)
+                        ),
+                        Extension::MetadataPointer {
+                            authority,
+                            address: metadata_address,
+                        } => format!(
+                            "{extensions}::metadata_pointer::instruction::initialize({id}, {address}, {}, {}).unwrap()",
+                            some(authority),
+                            some(metadata_address)
+                        ),
+                        Extension::TransferHook { authority, program } => format!(
+                            "{extensions}::transfer_hook::instruction::initialize({id}, {address}, {}, {}).unwrap()",
+                            some(authority),
+                            some(program)
+                        ),
+                        Extension::DefaultAccountState { frozen } => format!(
+                            "{extensions}::default_account_state::instruction::initialize_default_account_state({id}, {address}, &spl_token_2022::state::AccountState::{}).unwrap()",
+                            if *frozen { "Frozen" } else { "Initialized" }
+                        ),
+                        Extension::TokenMetadata {
+                            update_authority,
+                            name,
+                            symbol,
+                            uri,
+                            additional,
+                        } => {
+                            metadata.push(format!(
+                                "spl_token_metadata_interface::instruction::initialize({id}, {address}, &{}, {address}, &{}, {:?}.to_string(), {:?}.to_string(), {:?}.to_string())",
+                                key(update_authority),
+                                key(mint_authority),
+                                name,
+                                symbol,
+                                uri
+                            ));
+                            for (field, value) in additional {
+                                metadata.push(format!(
+                                    "spl_token_metadata_interface::instruction::update_field({id}, {address}, &{}, spl_token_metadata_interface::state::Field::Key({:?}.to_string()), {:?}.to_string())",
+                                    key(update_authority),
+                                    field,
+                                    value
+                                ));
+                            }
+                            // The metadata is written by the mint and update
+                            // authorities.
+                            sign(signer(mint_authority));
+                            sign(signer(update_authority));
+                            continue;
+                        }
+                    });
+                }
+                instructions.push(format!(
+                    "spl_token_2022::instruction::initialize_mint2({id}, {address}, &{}, {}, {decimals}).unwrap()",
+                    key(mint_authority),
+                    freeze_authority
+                        .as_ref()
+                        .map(|f| format!("Some(&{})", key(f)))
+                        .unwrap_or("None".to_string())
+                ));
+                instructions.extend(metadata);
+                sign(Some(format!("&{}", mint)));
+            }
+            SetupStep::CreateMint {
+                mint,
+                decimals,
+                mint_authority,
+                freeze_authority,
+                ..
+            } => {
+                let freeze_authority = match freeze_authority {
+                    Some(f) => format!("Some(&{})", key(f)),
+                    None => "None".to_string(),
+                };
+                instructions.push(format!(
+                    "system_instruction::create_account(&payer.pubkey(), &{mint}.pubkey(), rent.minimum_balance(Mint::LEN), Mint::LEN as u64, &spl_token::ID)"
+                ));
+                instructions.push(format!(
+                    "initialize_mint2(&spl_token::ID, &{mint}.pubkey(), &{}, {freeze_authority}, {decimals}).unwrap()",
+                    key(mint_authority)
+                ));
+                sign(Some(format!("&{}", mint)));
+            }
+            SetupStep::MintTo {
+                ata,
+                mint,
+                owner,
+                amount,
+                token_program,
+            } => {
+                let authority = plan.mint_authority(mint);
+                let (id, mint_to) = match token_program {
+                    TokenProgram::Token => ("spl_token::ID", "mint_to"),
+                    TokenProgram::Token2022 => {
+                        ("spl_token_2022::ID", "spl_token_2022::instruction::mint_to")
+                    }
+                };
+                instructions.push(format!(
+                    "create_associated_token_account_idempotent(&payer.pubkey(), &{}, &{mint}.pubkey(), &{id})",
+                    key(owner)
+                ));
+                instructions.push(format!(
+                    "{mint_to}(&{id}, &{mint}.pubkey(), &{ata}, &{}, &[], {amount}).unwrap()",
+                    key(&authority)
+                ));
+                sign(signer(&authority));
+            }
+            SetupStep::SetMintAuthority {
+                mint,
+                authority,
+                token_program,
+            } => {
+                let (id, set_authority) = match token_program {
+                    TokenProgram::Token => ("spl_token::ID", "spl_token::instruction"),
+                    TokenProgram::Token2022 => ("spl_token_2022::ID", "spl_token_2022::instruction"),
+                };
+                instructions.push(format!(
+                    "{set_authority}::set_authority(&{id}, &{mint}.pubkey(), Some(&{}), {set_authority}::AuthorityType::MintTokens, &payer.pubkey(), &[]).unwrap()",
+                    key(authority)
+                ));
+            }
+        }
+    }
+
+    (instructions, signers)
+}
+
+impl TestGenerator for RustProgramTest {
+    fn validate(&self, plan: &TestPlan) -> Result<(), LavaError> {
+        let mut errors = LavaError::default();
+        for t in &plan.tests {
+            for meta in t
+                .accounts
+                .iter()
+                .filter(|m| m.account.is_none() && !m.optional)
+            {
+                errors.push(
+                    LavaErrorKind::UnmappedAccount,
+                    format!("tests.{}.accounts.{}", t.name, meta.name),
+                    format!(
+                        "test \"{}\" doesn't resolve \"{}\" to an account, so its Rust test can't build the instruction",
+                        t.name, meta.name
+                    ),
+                );
+            }
+        }
+        errors.into_result()
+    }
+
+    fn generate(&self, plan: &TestPlan) -> String {
+        let programs = plan
+            .programs
+            .iter()
+            .map(|program| {
+                let id = match &program.source {
+                    ProgramSource::Workspace => {
+                        format!("{}::ID", program.idl.name.to_case(Case::Snake))
+                    }
+                    ProgramSource::Address(address) => {
+                        format!("Pubkey::from_str(\"{}\").unwrap()", address)
+                    }
+                };
+                format!("    let {} = {};", program_variable(&program.idl.name), id)
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        let add_programs = plan
+            .programs
+            .iter()
+            .map(|program| {
+                format!(
+                    "    program_test.add_program(\"{}\", {}, None);",
+                    program.idl.name.to_case(Case::Snake),
+                    program_variable(&program.idl.name)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let constants = plan.constants.iter().map(|c| {
+            format!(
+                "    let {} = {};",
+                c.identifier,
+                arg(&c.program.to_case(Case::Snake), &c.value)
+            )
+        });
+        let accounts = plan
+            .accounts
+            .iter()
+            .filter(|account| !account.is_deferred())
+            .map(|account| {
+                let id = &account.identifier;
+                match &account.kind {
+                    AccountKind::Wallet | AccountKind::Mint => {
+                        format!("    let {} = Keypair::new();", id)
+                    }
+                    AccountKind::Pda { .. } => format!("    {}", derive(account)),
+                    AccountKind::Ata {
+                        authority,
+                        mint,
+                        token_program: TokenProgram::Token,
+                    } => format!(
+                        "    let {} = get_associated_token_address(&{}, &{});",
+                        id,
+                        key(authority),
+                        key(mint)
+                    ),
+                    AccountKind::Ata {
+                        authority, mint, ..
+                    } => format!(
+                        "    let {} = get_associated_token_address_with_program_id(&{}, &{}, &spl_token_2022::ID);",
+                        id,
+                        key(authority),
+                        key(mint)
+                    ),
+                }
+            });
+        let accounts = constants
+            .chain(accounts)
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let (setup, setup_signers) = setup(plan);
+        let setup = if setup.is_empty() {
+            "".to_string()
+        } else {
+            format!(
+                r#"
+    // Setup
+    let rent = banks_client.get_rent().await.unwrap();
+    let setup = [
+{}
+    ];
+    let transaction = Transaction::new_signed_with_payer(
+        &setup,
+        Some(&payer.pubkey()),
+        &[{}],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+"#,
+                setup
+                    .iter()
+                    .map(|i| format!("        {},", i))
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+                setup_signers.join(", ")
+            )
+        };
+
+        let token_2022 = plan.uses_token_2022();
+        let tests =
+            plan.tests
+                .iter()
+                .filter_map(|t| {
+                    let krate = t.program.to_case(Case::Snake);
+                    let mut metas = vec![];
+                    let mut signers = vec!["&payer".to_string()];
+                    for meta in &t.accounts {
+                        let account = match &meta.account {
+                            Some(account) => account.clone(),
+                            None if meta.optional => AccountRef::Program(t.program.clone()),
+                            None => return None,
+                        };
+                        let constructor = if meta.writable { "new" } else { "new_readonly" };
+                        metas.push(format!(
+                            "                AccountMeta::{}({}, {}),",
+                            constructor,
+                            key(&account),
+                            meta.signer
+                        ));
+                        if let Some(signer) =
+                            signer(&account).filter(|s| meta.signer && !signers.contains(s))
+                        {
+                            signers.push(signer);
+                        }
+                    }
+
+                    let discriminator = match plan
+                        .program(&t.program)
+                        .and_then(|p| p.idl.discriminator(&t.instruction))
+                    {
+                        Some(bytes) => format!(
+                            "vec![{}],",
+                            bytes
+                                .iter()
+                                .map(|b| format!("{}u8", b))
+                                .collect::<Vec<String>>()
+                                .join(", ")
+                        ),
+                        None => format!(
+                            "sighash(\"{}\").to_vec(),",
+                            t.instruction.to_case(Case::Snake)
+                        ),
+                    };
+                    let data = std::iter::once(format!("                {}", discriminator))
+                        .chain(t.args.iter().map(|a| {
+                            format!(
+                                "                AnchorSerialize::try_to_vec(&{}).unwrap(),",
+                                a.constant.clone().unwrap_or_else(|| arg(&krate, &a.value))
+                            )
+                        }))
+                        .collect::<Vec<String>>()
+                        .join("\n");
+
+                    let mut process = match &t.expect {
+                        Some(expected) => format!(
+                            r#"let error = banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap();
+        // {}
+        assert_eq!(
+            error,
+            TransactionError::InstructionError(0, InstructionError::Custom({}))
+        );"#,
+                            expected.name.as_deref().unwrap_or("Custom error"),
+                            expected.code
+                        ),
+                        None => "banks_client.process_transaction(transaction).await.unwrap();"
+                            .to_string(),
+                    };
+                    if !t.deltas.is_empty() {
+                        process = format!(
+                            "{}\n        {}\n        {}{}",
+                            snapshot(t, "before", token_2022),
+                            process,
+                            snapshot(t, "after", token_2022),
+                            deltas(t)
+                        );
+                    }
+                    process.push_str(&assertions(t, token_2022));
+                    let derive = plan
+                        .accounts
+                        .iter()
+                        .filter(|a| a.is_deferred() && t.uses(&a.identifier))
+                        .map(|a| format!("\n        {}", derive(a)))
+                        .collect::<String>();
+
+                    Some(format!(
+                        r#"
+    // {}
+    {{{}
+        let instruction = Instruction {{
+            program_id: {},
+            accounts: vec![
+{}
+            ],
+            data: [
+{}
+            ]
+            .concat(),
+        }};
+        let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[{}],
+            recent_blockhash,
+        );
+        {}
+    }}"#,
+                        t.name,
+                        derive,
+                        program_variable(&t.program),
+                        metas.join("\n"),
+                        data,
+                        signers.join(", "),
+                        process
+                    ))
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+
+        let spl = plan.uses_spl()
+            || plan.tests.iter().any(|t| {
+                t.asserts
+                    .iter()
+                    .any(|a| matches!(a, Assertion::TokenAmount { .. }))
+                    || t.deltas.iter().any(|d| d.tokens)
+            });
+        let import_spl = if !spl {
+            ""
+        } else {
+            r#"use solana_sdk::program_pack::Pack;
+use spl_associated_token_account::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token::{
+    instruction::{initialize_mint2, mint_to},
+    state::Mint,
+};
+"#
+        };
+        let name = plan.name.to_case(Case::Snake);
+        // 0.30 IDLs spell out their discriminators.
+        let sighash = if plan
+            .programs
+            .iter()
+            .any(|p| p.idl.format == IdlFormat::Legacy)
+        {
+            r#"/// Anchor's instruction discriminator.
+fn sighash(name: &str) -> [u8; 8] {
+    let preimage = format!("global:{}", name);
+    hash(preimage.as_bytes()).to_bytes()[..8].try_into().unwrap()
+}
+
+"#
+        } else {
+            ""
+        };
+
+        format!(
+            r#"#![allow(unused_imports)]
+
+use anchor_lang::{{solana_program::hash::hash, AccountDeserialize, AnchorSerialize}};
+use solana_program_test::{{tokio, ProgramTest}};
+use solana_sdk::{{
+    instruction::{{AccountMeta, Instruction, InstructionError}},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{{Keypair, Signer}},
+    system_instruction, system_program,
+    transaction::{{Transaction, TransactionError}},
+}};
+use std::str::FromStr;
+{import_spl}
+{sighash}#[tokio::test]
+async fn {name}() {{
+{programs}
+    let mut program_test = ProgramTest::default();
+{add_programs}
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Accounts
+{accounts}
+{setup}{tests}
+}}
+"#
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use serde_json::json;
+    use soda_sol::structs::InstructionType;
+
+    use super::{arg, setup, RustProgramTest};
+    use crate::codegen::TestGenerator;
+    use crate::error::LavaErrorKind;
+
+    #[test]
+    fn renders_program_test() {
+        let rust = fixtures::config(&fixtures::escrow())
+            .try_generate(&RustProgramTest)
+            .unwrap();
+        for expected in [
+            "let anchor_escrow_program = anchor_escrow::ID;",
+            r#"program_test.add_program("anchor_escrow", anchor_escrow_program, None);"#,
+            r#"let (escrow, _) = Pubkey::find_program_address(&["escrow".as_bytes(), maker.pubkey().as_ref(), &1u64.to_le_bytes()], &anchor_escrow_program);"#,
+            "let vault = get_associated_token_address(&escrow, &token_a.pubkey());",
+            "system_instruction::transfer(&payer.pubkey(), &maker.pubkey(), 10 * LAMPORTS_PER_SOL),",
+            "initialize_mint2(&spl_token::ID, &token_a.pubkey(), &payer.pubkey(), None, 6).unwrap(),",
+            "mint_to(&spl_token::ID, &token_a.pubkey(), &maker_ata_a, &payer.pubkey(), &[], 1000000000).unwrap(),",
+            "AccountMeta::new(maker.pubkey(), true),",
+            "AccountMeta::new_readonly(spl_token::ID, false),",
+            r#"sighash("make").to_vec(),"#,
+            "AnchorSerialize::try_to_vec(&1000000u64).unwrap(),",
+            "&[&payer, &maker],",
+            "&[&payer, &taker],",
+        ] {
+            assert!(rust.contains(expected), "missing {}", expected);
+        }
+        // Each mint is created once, however many ATAs it funds.
+        assert_eq!(rust.matches("initialize_mint2(").count(), 2);
+    }
+
+    #[test]
+    fn orders_setup_and_account_metas() {
+        let mut json = fixtures::escrow();
+        json["accounts"][2]["mint_authority"] = "Escrow".into();
+        let config = fixtures::config(&json);
+
+        // Mints are created before they are minted to, and a PDA only gets
+        // its mint once its ATAs are funded.
+        let (instructions, signers) = setup(&config.plan().unwrap());
+        let calls: Vec<&str> = instructions
+            .iter()
+            .map(|i| &i[..i.find('(').unwrap()])
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                "system_instruction::transfer",
+                "system_instruction::transfer",
+                "system_instruction::create_account",
+                "initialize_mint2",
+                "system_instruction::create_account",
+                "initialize_mint2",
+                "create_associated_token_account_idempotent",
+                "mint_to",
+                "create_associated_token_account_idempotent",
+                "mint_to",
+                "spl_token::instruction::set_authority",
+            ]
+        );
+        assert_eq!(signers, ["&payer", "&token_a", "&token_b"]);
+
+        // Metas follow the IDL's order, whatever order the test maps them in.
+        let rust = config.try_to_rust_tests().unwrap();
+        assert!(rust.contains(
+            r#"            program_id: anchor_escrow_program,
+            accounts: vec![
+                AccountMeta::new(taker.pubkey(), true),
+                AccountMeta::new_readonly(maker.pubkey(), false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(token_a.pubkey(), false),
+                AccountMeta::new_readonly(token_b.pubkey(), false),
+                AccountMeta::new(maker_ata_b, false),
+                AccountMeta::new(taker_ata_a, false),
+                AccountMeta::new(taker_ata_b, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: [
+                sighash("take").to_vec(),
+            ]"#
+        ));
+    }
+
+    #[test]
+    fn reports_unresolved_accounts() {
+        let mut plan = fixtures::config(&fixtures::escrow()).plan().unwrap();
+        assert!(RustProgramTest.validate(&plan).is_ok());
+        plan.tests[1].accounts[1].account = None;
+        let err = RustProgramTest.validate(&plan).unwrap_err();
+        assert_eq!(err.issues()[0].kind, LavaErrorKind::UnmappedAccount);
+        assert_eq!(err.issues()[0].path, "tests.Take.accounts.maker");
+    }
+
+    #[test]
+    fn passes_the_program_for_optional_accounts() {
+        let mut json = fixtures::escrow();
+        json["idls"][0]["instructions"][0]["accounts"][3]["isOptional"] = json!(true);
+        json["tests"][0]["accounts"]
+            .as_object_mut()
+            .unwrap()
+            .remove("mintB");
+        let rust = fixtures::config(&json).try_to_rust_tests().unwrap();
+        // Anchor reads the program id as `None`, in the IDL's position.
+        assert!(rust.contains(
+            r#"            program_id: anchor_escrow_program,
+            accounts: vec![
+                AccountMeta::new(maker.pubkey(), true),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(token_a.pubkey(), false),
+                AccountMeta::new_readonly(anchor_escrow_program, false),
+                AccountMeta::new(maker_ata_a, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],"#
+        ));
+    }
+
+    #[test]
+    fn encodes_rust_args() {
+        let config = fixtures::config(&fixtures::escrow());
+        let idl = &config.idls[0];
+        let encode = |kind, value| {
+            let kind: InstructionType = serde_json::from_value(kind).unwrap();
+            config
+                .arg_value(idl, &kind, &value)
+                .map(|v| arg("anchor_escrow", &v))
+        };
+        assert_eq!(encode(json!("i16"), json!(-3)).unwrap(), "-3i16");
+        assert_eq!(
+            encode(json!("string"), json!("a \"b\"")).unwrap(),
+            r#"String::from("a \"b\"")"#
+        );
+        assert_eq!(
+            encode(json!("publicKey"), json!("Escrow")).unwrap(),
+            "escrow"
+        );
+        assert_eq!(
+            encode(json!("bytes"), json!("0xdead")).unwrap(),
+            "vec![222u8, 173u8]"
+        );
+        assert_eq!(
+            encode(json!({ "option": { "vec": "u8" } }), json!(null)).unwrap(),
+            "None::<Vec<u8>>"
+        );
+        assert_eq!(
+            encode(json!({ "vec": "u64" }), json!([])).unwrap(),
+            "Vec::<u64>::new()"
+        );
+        assert_eq!(
+            encode(json!({ "tuple": ["bool"] }), json!([true])).unwrap(),
+            "(true,)"
+        );
+        assert!(encode(json!("bytes"), json!("0xdea")).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+/// The category of a [`LavaIssue`].
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LavaErrorKind {
+    /// The workspace could not be parsed at all.
+    InvalidSchema,
+    /// An account whose `kind` is none of `wallet`, `mint`, `ata`, `pda` or
+    /// `program`.
+    UnknownKind,
+    /// Two accounts collapse to the same generated identifier.
+    DuplicateName,
+    /// A reference to an account that is not declared in the workspace.
+    UnknownAccount,
+    /// A reference to a program that is neither declared nor has an IDL.
+    UnknownProgram,
+    /// A PDA or ATA seed whose value can't be encoded or looked up, like a
+    /// `usize` too wide for the workspace's `usizeWidth` or an arg no test
+    /// passes.
+    InvalidSeed,
+    /// A PDA's seeds disagree with the IDL `pda` seeds of an instruction
+    /// account a test maps it to.
+    SeedMismatch,
+    /// A test calls an instruction that is not in the program's IDL.
+    UnknownInstruction,
+    /// A test maps an account the IDL instruction does not take.
+    UnknownInstructionAccount,
+    /// A test leaves out an instruction account that is neither optional,
+    /// pinned to an address by the IDL nor named after a builtin.
+    UnmappedAccount,
+    /// A test maps an instruction signer to an account lava holds no keypair
+    /// for, like a PDA or an ATA.
+    InvalidSigner,
+    /// A test's `accounts` field is not an object of names.
+    InvalidAccounts,
+    /// A test passes a different number of args than the IDL instruction takes.
+    ArgCount,
+    /// A test arg can't be encoded as its IDL type.
+    InvalidArg,
+    /// A mint or ATA whose token settings don't fit together, like a
+    /// Token-2022 extension on a token program mint or an ATA under another
+    /// token program than its mint.
+    InvalidToken,
+    /// A program address that isn't a base58 encoded 32 byte key.
+    InvalidAddress,
+    /// A test expects an error that neither the program nor Anchor declares.
+    UnknownError,
+    /// A test assertion or delta that is malformed or doesn't fit the IDL
+    /// account it checks.
+    InvalidAssert,
+}
+
+/// A single problem found in a workspace.
+///
+/// `path` locates the offending field, e.g. `tests.Make.accounts.vault`.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LavaIssue {
+    pub kind: LavaErrorKind,
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for LavaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Every problem found while checking a workspace.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LavaError {
+    issues: Vec<LavaIssue>,
+}
+
+impl LavaError {
+    pub fn issues(&self) -> &[LavaIssue] {
+        &self.issues
+    }
+
+    pub(crate) fn single(kind: LavaErrorKind, path: String, message: String) -> Self {
+        let mut error = LavaError::default();
+        error.push(kind, path, message);
+        error
+    }
+
+    pub(crate) fn push(&mut self, kind: LavaErrorKind, path: String, message: String) {
+        self.issues.push(LavaIssue {
+            kind,
+            path,
+            message,
+        });
+    }
+
+    pub(crate) fn extend(&mut self, other: LavaError) {
+        self.issues.extend(other.issues);
+    }
+
+    pub(crate) fn into_result(self) -> Result<(), LavaError> {
+        if self.issues.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for LavaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let issues = self
+            .issues
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "{}", issues.join("\n"))
+    }
+}
+
+impl std::error::Error for LavaError {}
+
+#[wasm_bindgen]
+impl LavaError {
+    #[wasm_bindgen(getter = issues)]
+    pub fn js_issues(&self) -> Vec<LavaIssue> {
+        self.issues.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl From<serde_json::Error> for LavaError {
+    fn from(e: serde_json::Error) -> Self {
+        LavaError::single(
+            LavaErrorKind::InvalidSchema,
+            format!("{}:{}", e.line(), e.column()),
+            e.to_string(),
+        )
+    }
+}
+
+impl From<anyhow::Error> for LavaError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<LavaError>() {
+            Ok(e) => e,
+            Err(e) => LavaError::single(LavaErrorKind::InvalidSchema, String::new(), e.to_string()),
+        }
+    }
+}
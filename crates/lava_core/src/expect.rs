@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+use crate::codegen::ExpectedError;
+use crate::idl::Idl;
+
+/// Errors every Anchor program can fail with, from `anchor_lang::error::ErrorCode`
+/// as of Anchor 0.31.
+const ANCHOR_ERRORS: [(&str, u32); 78] = [
+    ("InstructionMissing", 100),
+    ("InstructionFallbackNotFound", 101),
+    ("InstructionDidNotDeserialize", 102),
+    ("InstructionDidNotSerialize", 103),
+    ("IdlInstructionStub", 1000),
+    ("IdlInstructionInvalidProgram", 1001),
+    ("IdlAccountNotEmpty", 1002),
+    ("EventInstructionStub", 1500),
+    ("ConstraintMut", 2000),
+    ("ConstraintHasOne", 2001),
+    ("ConstraintSigner", 2002),
+    ("ConstraintRaw", 2003),
+    ("ConstraintOwner", 2004),
+    ("ConstraintRentExempt", 2005),
+    ("ConstraintSeeds", 2006),
+    ("ConstraintExecutable", 2007),
+    ("ConstraintState", 2008),
+    ("ConstraintAssociated", 2009),
+    ("ConstraintAssociatedInit", 2010),
+    ("ConstraintClose", 2011),
+    ("ConstraintAddress", 2012),
+    ("ConstraintZero", 2013),
+    ("ConstraintTokenMint", 2014),
+    ("ConstraintTokenOwner", 2015),
+    ("ConstraintMintMintAuthority", 2016),
+    ("ConstraintMintFreezeAuthority", 2017),
+    ("ConstraintMintDecimals", 2018),
+    ("ConstraintSpace", 2019),
+    ("ConstraintAccountIsNone", 2020),
+    ("ConstraintTokenTokenProgram", 2021),
+    ("ConstraintMintTokenProgram", 2022),
+    ("ConstraintAssociatedTokenTokenProgram", 2023),
+    ("ConstraintMintGroupPointerExtension", 2024),
+    ("ConstraintMintGroupPointerExtensionAuthority", 2025),
+    ("ConstraintMintGroupPointerExtensionGroupAddress", 2026),
+    ("ConstraintMintGroupMemberPointerExtension", 2027),
+    ("ConstraintMintGroupMemberPointerExtensionAuthority", 2028),
+    (
+        "ConstraintMintGroupMemberPointerExtensionMemberAddress",
+        2029,
+    ),
+    ("ConstraintMintMetadataPointerExtension", 2030),
+    ("ConstraintMintMetadataPointerExtensionAuthority", 2031),
+    (
+        "ConstraintMintMetadataPointerExtensionMetadataAddress",
+        2032,
+    ),
+    ("ConstraintMintCloseAuthorityExtension", 2033),
+    ("ConstraintMintCloseAuthorityExtensionAuthority", 2034),
+    ("ConstraintMintPermanentDelegateExtension", 2035),
+    ("ConstraintMintPermanentDelegateExtensionDelegate", 2036),
+    ("ConstraintMintTransferHookExtension", 2037),
+    ("ConstraintMintTransferHookExtensionAuthority", 2038),
+    ("ConstraintMintTransferHookExtensionProgramId", 2039),
+    ("ConstraintDuplicateMutableAccount", 2040),
+    ("RequireViolated", 2500),
+    ("RequireEqViolated", 2501),
+    ("RequireKeysEqViolated", 2502),
+    ("RequireNeqViolated", 2503),
+    ("RequireKeysNeqViolated", 2504),
+    ("RequireGtViolated", 2505),
+    ("RequireGteViolated", 2506),
+    ("AccountDiscriminatorAlreadySet", 3000),
+    ("AccountDiscriminatorNotFound", 3001),
+    ("AccountDiscriminatorMismatch", 3002),
+    ("AccountDidNotDeserialize", 3003),
+    ("AccountDidNotSerialize", 3004),
+    ("AccountNotEnoughKeys", 3005),
+    ("AccountNotMutable", 3006),
+    ("AccountOwnedByWrongProgram", 3007),
+    ("InvalidProgramId", 3008),
+    ("InvalidProgramExecutable", 3009),
+    ("AccountNotSigner", 3010),
+    ("AccountNotSystemOwned", 3011),
+    ("AccountNotInitialized", 3012),
+    ("AccountNotProgramData", 3013),
+    ("AccountNotAssociatedTokenAccount", 3014),
+    ("AccountSysvarMismatch", 3015),
+    ("AccountReallocExceedsLimit", 3016),
+    ("AccountDuplicateReallocs", 3017),
+    ("DeclaredProgramIdMismatch", 4100),
+    ("TryingToInitPayerAsProgramAccount", 4101),
+    ("InvalidNumericConversion", 4102),
+    ("Deprecated", 5000),
+];
+
+/// What a test expects its call to fail with. Tests without one must
+/// succeed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LavaExpect {
+    pub(crate) error: LavaErrorCode,
+}
+
+/// An Anchor error, by the name it is declared with or by its code.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum LavaErrorCode {
+    Code(u32),
+    Name(String),
+}
+
+/// The `errors` the program declares, as `(name, code)`.
+///
+/// `soda_sol` keeps them private, so they are read out of the IDL's
+/// normalized JSON.
+fn idl_errors(idl: &Idl) -> Vec<(String, u32)> {
+    let idl = idl.json();
+    idl["errors"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|e| Some((e["name"].as_str()?.to_string(), e["code"].as_u64()? as u32)))
+        .collect()
+}
+
+impl LavaExpect {
+    /// Resolves the expected error against the program's own errors, then
+    /// Anchor's. Unknown codes are kept, since programs can return custom
+    /// codes they don't declare, but unknown names are an error.
+    pub(crate) fn resolve(&self, idl: &Idl) -> Result<ExpectedError, String> {
+        let errors = idl_errors(idl);
+        let known = errors
+            .iter()
+            .map(|(name, code)| (name.as_str(), *code))
+            .chain(ANCHOR_ERRORS);
+        match &self.error {
+            LavaErrorCode::Code(code) => Ok(ExpectedError {
+                code: *code,
+                name: known
+                    .into_iter()
+                    .find(|(_, c)| c == code)
+                    .map(|(n, _)| n.to_string()),
+            }),
+            LavaErrorCode::Name(name) => known
+                .into_iter()
+                .find(|(n, _)| n == name)
+                .map(|(n, code)| ExpectedError {
+                    code,
+                    name: Some(n.to_string()),
+                })
+                .ok_or(format!(
+                    "\"{}\" is neither an error of \"{}\" nor an Anchor error",
+                    name, idl.name
+                )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::ExpectedError;
+    use crate::fixtures;
+    use crate::idl::Idl;
+    use serde_json::{json, Value};
+
+    use super::LavaExpect;
+
+    fn resolve(error: Value) -> Result<(u32, Option<String>), String> {
+        let mut idl = fixtures::escrow_make()["idls"][0].clone();
+        idl["errors"] = json!([{ "code": 6000, "name": "AlreadyTaken", "msg": "Already taken" }]);
+        let idl: Idl = serde_json::from_value(idl).unwrap();
+        let expect: LavaExpect = serde_json::from_value(json!({ "error": error })).unwrap();
+        expect.resolve(&idl).map(|e| (e.code, e.name))
+    }
+
+    #[test]
+    fn resolves_expected_errors() {
+        assert_eq!(
+            resolve(json!("ConstraintSeeds")),
+            Ok((2006, Some("ConstraintSeeds".into())))
+        );
+        assert_eq!(
+            resolve(json!("AlreadyTaken")),
+            Ok((6000, Some("AlreadyTaken".into())))
+        );
+        assert_eq!(
+            resolve(json!(6000)),
+            Ok((6000, Some("AlreadyTaken".into())))
+        );
+        assert_eq!(
+            resolve(json!("ConstraintDuplicateMutableAccount")),
+            Ok((2040, Some("ConstraintDuplicateMutableAccount".into())))
+        );
+        assert_eq!(
+            resolve(json!(4102)),
+            Ok((4102, Some("InvalidNumericConversion".into())))
+        );
+        assert_eq!(resolve(json!(6042)), Ok((6042, None)));
+        assert!(resolve(json!("AlreadyTakn")).is_err());
+    }
+
+    #[test]
+    fn plans_expected_errors() {
+        let mut json = fixtures::escrow();
+        let mut take_twice = json["tests"][1].clone();
+        take_twice["name"] = "Take twice".into();
+        take_twice["expect"] = json!({ "error": "AccountNotInitialized" });
+        json["tests"].as_array_mut().unwrap().push(take_twice);
+        let plan = fixtures::config(&json).plan().unwrap();
+
+        let expected: Vec<_> = plan.tests.iter().map(|t| t.expect.clone()).collect();
+        assert_eq!(
+            expected,
+            [
+                None,
+                None,
+                Some(ExpectedError {
+                    code: 3012,
+                    name: Some("AccountNotInitialized".into())
+                })
+            ]
+        );
+    }
+}
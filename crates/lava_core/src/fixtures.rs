@@ -0,0 +1,54 @@
+//! The example workspaces unit tests start from, as JSON to edit before
+//! turning them into a [`LavaConfig`].
+
+use serde_json::Value;
+
+use crate::{LavaConfig, LavaConfigJSON};
+
+fn parse(example: &str) -> Value {
+    serde_json::from_str(example).unwrap()
+}
+
+/// Two tests, making an escrow and taking it.
+pub(crate) fn escrow() -> Value {
+    parse(include_str!(
+        "../../../workspaces_examples/Escrow2024-Make-and-Take.json"
+    ))
+}
+
+/// The Make test of [`escrow`] alone.
+pub(crate) fn escrow_make() -> Value {
+    parse(include_str!(
+        "../../../workspaces_examples/Escrow2024-Make.json"
+    ))
+}
+
+/// [`escrow_make`] with its program accounts left for the builtins to fill.
+pub(crate) fn escrow_make_unmapped() -> Value {
+    let mut workspace = escrow_make();
+    let accounts = workspace["tests"][0]["accounts"].as_object_mut().unwrap();
+    for program in ["tokenProgram", "associatedTokenProgram", "systemProgram"] {
+        accounts.remove(program);
+    }
+    workspace
+}
+
+/// [`escrow_make`] with an Anchor 0.30 IDL.
+pub(crate) fn escrow_anchor_030() -> Value {
+    parse(include_str!(
+        "../../../workspaces_examples/Escrow2024-Make-Anchor030.json"
+    ))
+}
+
+/// Initializing a dice game, with a vault PDA seeded by the house.
+pub(crate) fn dice() -> Value {
+    parse(include_str!(
+        "../../../workspaces_examples/Dice-Initialize.json"
+    ))
+}
+
+/// The config of `workspace`, which must pass every check.
+pub(crate) fn config(workspace: &Value) -> LavaConfig {
+    let json: LavaConfigJSON = serde_json::from_value(workspace.clone()).unwrap();
+    LavaConfig::try_from(&json).unwrap()
+}
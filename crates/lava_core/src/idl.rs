@@ -0,0 +1,334 @@
+//! Program IDLs of either Anchor generation. Anchor 0.30 reworked the IDL
+//! format; both are normalized to the legacy shape `soda_sol` models, and
+//! [`Idl`] remembers which one it was given so generators can target the
+//! matching client.
+
+use std::ops::Deref;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+use soda_sol::IDL;
+
+/// The IDL format a program was built with, which decides the Anchor client
+/// API generated tests use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdlFormat {
+    /// Anchor before 0.30: `isMut`/`isSigner`, camelCase names and the
+    /// program address in `metadata.address`.
+    Legacy,
+    /// Anchor 0.30 and later, marked by `metadata.spec`: `writable`/`signer`,
+    /// snake_case names, a top-level `address`, explicit discriminators and
+    /// account layouts in `types`.
+    Spec,
+}
+
+/// A program IDL, readable as a legacy [`IDL`] whichever format it came in.
+#[derive(Debug, Clone)]
+pub struct Idl {
+    idl: IDL,
+    /// The normalized IDL as JSON, which keeps what `soda_sol` hides or
+    /// drops: type layouts, errors, `pda` seeds, fixed account addresses and
+    /// discriminators.
+    json: Value,
+    /// The IDL as given, which is what it is exported and embedded as.
+    source: Value,
+    pub format: IdlFormat,
+}
+
+impl Deref for Idl {
+    type Target = IDL;
+
+    fn deref(&self) -> &IDL {
+        &self.idl
+    }
+}
+
+impl Idl {
+    pub(crate) fn json(&self) -> &Value {
+        &self.json
+    }
+
+    /// The IDL as it was given.
+    pub fn source(&self) -> &Value {
+        &self.source
+    }
+
+    /// The discriminator the IDL declares for `instruction`. Legacy IDLs
+    /// leave it implied by the instruction name.
+    pub fn discriminator(&self, instruction: &str) -> Option<Vec<u8>> {
+        self.json["instructions"]
+            .as_array()?
+            .iter()
+            .find(|i| i["name"] == instruction)?["discriminator"]
+            .as_array()?
+            .iter()
+            .map(|b| b.as_u64().map(|b| b as u8))
+            .collect()
+    }
+
+    fn instruction_account(&self, instruction: &str, account: &str) -> Option<&Value> {
+        self.json["instructions"]
+            .as_array()?
+            .iter()
+            .find(|i| i["name"] == instruction)?["accounts"]
+            .as_array()?
+            .iter()
+            .find(|a| a["name"] == account)
+    }
+
+    /// The address the IDL pins `account` of `instruction` to, like the
+    /// token program's for a `Program<'info, Token>`.
+    pub fn fixed_address(&self, instruction: &str, account: &str) -> Option<&str> {
+        self.instruction_account(instruction, account)?["address"].as_str()
+    }
+
+    /// Whether `account` of `instruction` may be left out.
+    pub fn is_optional(&self, instruction: &str, account: &str) -> bool {
+        self.instruction_account(instruction, account)
+            .is_some_and(|a| a["isOptional"].as_bool() == Some(true))
+    }
+}
+
+impl TryFrom<Value> for Idl {
+    type Error = serde_json::Error;
+
+    fn try_from(source: Value) -> Result<Self, Self::Error> {
+        let format = if source["metadata"]["spec"].is_string() {
+            IdlFormat::Spec
+        } else {
+            IdlFormat::Legacy
+        };
+        let json = match format {
+            IdlFormat::Legacy => source.clone(),
+            IdlFormat::Spec => legacy(&source),
+        };
+        // `soda_sol` can't parse every seed shape, and lava reads seeds from
+        // `json` anyway.
+        let mut parsed = json.clone();
+        for instruction in parsed["instructions"].as_array_mut().into_iter().flatten() {
+            for account in instruction["accounts"].as_array_mut().into_iter().flatten() {
+                if let Some(account) = account.as_object_mut() {
+                    account.remove("pda");
+                }
+            }
+        }
+        Ok(Idl {
+            idl: serde_json::from_value(parsed)?,
+            json,
+            source,
+            format,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Idl {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Idl::try_from(Value::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for Idl {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.source.serialize(serializer)
+    }
+}
+
+/// A 0.30 type in legacy form: `pubkey` is `publicKey` and `defined` names
+/// the type directly.
+fn legacy_type(kind: &Value) -> Value {
+    match kind {
+        Value::String(s) if s == "pubkey" => json!("publicKey"),
+        Value::Object(o) => Value::Object(
+            o.iter()
+                .map(|(key, value)| {
+                    let value = match (key.as_str(), value) {
+                        ("defined", Value::Object(d)) => d["name"].clone(),
+                        _ => legacy_type(value),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(legacy_type).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Struct or variant fields in legacy form. Tuple fields, which 0.30 lists
+/// as bare types, are named by position.
+fn legacy_fields(fields: &Value) -> Vec<Value> {
+    fields
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(i, field)| match field.get("type") {
+            Some(kind) => json!({ "name": field["name"], "type": legacy_type(kind) }),
+            None => json!({ "name": i.to_string(), "type": legacy_type(field) }),
+        })
+        .collect()
+}
+
+fn legacy_type_def(ty: &Value) -> Value {
+    let mut def = json!({ "kind": ty["kind"] });
+    if !ty["fields"].is_null() {
+        def["fields"] = json!(legacy_fields(&ty["fields"]));
+    }
+    if let Some(variants) = ty["variants"].as_array() {
+        def["variants"] = variants
+            .iter()
+            .map(|v| match v.get("fields") {
+                Some(fields) => json!({ "name": v["name"], "fields": legacy_fields(fields) }),
+                None => json!({ "name": v["name"] }),
+            })
+            .collect();
+    }
+    def
+}
+
+/// Instruction accounts in legacy form, with composite account groups
+/// flattened.
+fn legacy_accounts(accounts: &Value, out: &mut Vec<Value>) {
+    for account in accounts.as_array().into_iter().flatten() {
+        if !account["accounts"].is_null() {
+            legacy_accounts(&account["accounts"], out);
+            continue;
+        }
+        let mut legacy = json!({
+            "name": account["name"],
+            "isMut": account["writable"].as_bool().unwrap_or(false),
+            "isSigner": account["signer"].as_bool().unwrap_or(false),
+        });
+        if account["optional"].as_bool() == Some(true) {
+            legacy["isOptional"] = json!(true);
+        }
+        if !account["address"].is_null() {
+            legacy["address"] = account["address"].clone();
+        }
+        if !account["pda"].is_null() {
+            legacy["pda"] = json!({ "seeds": account["pda"]["seeds"] });
+            if !account["pda"]["program"].is_null() {
+                legacy["pda"]["programId"] = account["pda"]["program"].clone();
+            }
+        }
+        out.push(legacy);
+    }
+}
+
+/// Rewrites a 0.30 IDL in the legacy shape, keeping discriminators.
+fn legacy(source: &Value) -> Value {
+    let types = source["types"].as_array().cloned().unwrap_or_default();
+    let layout = |name: &Value| {
+        types
+            .iter()
+            .find(|t| &t["name"] == name)
+            .map(|t| legacy_type_def(&t["type"]))
+            .unwrap_or(json!({ "kind": "struct", "fields": [] }))
+    };
+    let list = |section: &str| source[section].as_array().cloned().unwrap_or_default();
+
+    json!({
+        "version": source["metadata"]["version"],
+        "name": source["metadata"]["name"],
+        "instructions": list("instructions")
+            .iter()
+            .map(|i| {
+                let mut accounts = vec![];
+                legacy_accounts(&i["accounts"], &mut accounts);
+                json!({
+                    "name": i["name"],
+                    "discriminator": i["discriminator"],
+                    "accounts": accounts,
+                    "args": legacy_fields(&i["args"]),
+                })
+            })
+            .collect::<Vec<Value>>(),
+        "accounts": list("accounts")
+            .iter()
+            .map(|a| json!({
+                "name": a["name"],
+                "discriminator": a["discriminator"],
+                "type": layout(&a["name"]),
+            }))
+            .collect::<Vec<Value>>(),
+        "types": types
+            .iter()
+            .map(|t| json!({ "name": t["name"], "type": legacy_type_def(&t["type"]) }))
+            .collect::<Vec<Value>>(),
+        "events": list("events")
+            .iter()
+            .map(|e| {
+                let fields = layout(&e["name"])["fields"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|f| json!({ "name": f["name"], "type": f["type"], "index": false }))
+                    .collect::<Vec<Value>>();
+                json!({ "name": e["name"], "discriminator": e["discriminator"], "fields": fields })
+            })
+            .collect::<Vec<Value>>(),
+        "errors": list("errors"),
+        "metadata": {
+            "address": source["address"],
+            "name": source["metadata"]["name"],
+            "version": source["metadata"]["version"],
+            "spec": source["metadata"]["spec"],
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use serde_json::Value;
+
+    use super::{Idl, IdlFormat};
+    use crate::args::{defined_type, DefinedType};
+
+    fn example(workspace: Value) -> Idl {
+        serde_json::from_value(workspace["idls"][0].clone()).unwrap()
+    }
+
+    #[test]
+    fn normalizes_both_generations() {
+        let legacy = example(fixtures::escrow_make());
+        let spec = example(fixtures::escrow_anchor_030());
+        assert_eq!(legacy.format, IdlFormat::Legacy);
+        assert_eq!(spec.format, IdlFormat::Spec);
+
+        assert_eq!(spec.name, legacy.name);
+        assert_eq!(spec.metadata.address, legacy.metadata.address);
+        let flags = |idl: &Idl| {
+            idl.instructions[0]
+                .accounts
+                .iter()
+                .map(|a| (a.isMut, a.isSigner))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(flags(&spec), flags(&legacy));
+        assert_eq!(
+            spec.json()["instructions"][0]["accounts"][6]["address"],
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+        );
+
+        let Ok(DefinedType::Struct(fields)) = defined_type(&spec, "Escrow") else {
+            panic!("expected the Escrow layout");
+        };
+        assert_eq!(fields[1].0, "mint_a");
+        assert!(matches!(
+            fields[1].1,
+            soda_sol::structs::InstructionType::PublicKey
+        ));
+
+        assert_eq!(
+            spec.discriminator("make"),
+            Some(vec![138, 227, 232, 77, 223, 166, 96, 197])
+        );
+        assert_eq!(legacy.discriminator("make"), None);
+
+        assert_eq!(
+            serde_json::to_value(&spec).unwrap()["metadata"]["spec"],
+            "0.1.0"
+        );
+    }
+}
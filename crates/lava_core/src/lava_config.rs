@@ -1,351 +1,135 @@
 use convert_case::Case;
 
 use anyhow::Result;
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
 use anyhow::Error;
 use convert_case::Casing;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
-use soda_sol::IDL;
 use wasm_bindgen::prelude::*;
 
-use crate::seeds::LavaSeed;
+use crate::asserts::{LavaAssert, LavaDelta};
+use crate::check::account_key;
+use crate::error::{LavaError, LavaErrorKind};
+use crate::expect::LavaExpect;
+use crate::idl::Idl;
+use crate::seeds::{LavaSeed, DEFAULT_USIZE_WIDTH};
+use crate::token::{LavaExtension, TokenProgram};
+
+/// The variable a program is bound to in generated tests. Each program gets
+/// its own so workspaces can call more than one.
+pub(crate) fn program_variable(name: &str) -> String {
+    format!("{}_program", name.to_case(Case::Snake))
+}
 
 #[wasm_bindgen]
 impl LavaConfig {
-    pub(crate) fn check(&self) -> Result<()> {
-        // TODO: Make this actually check our Schema for problems
-        Ok(())
+    /// Checks that every reference in the workspace resolves, without
+    /// generating anything.
+    #[wasm_bindgen]
+    pub fn validate(&self) -> Result<(), LavaError> {
+        self.check()
     }
+}
 
-    #[wasm_bindgen]
-    pub fn to_mocha(&self) -> String {
-        let mut accounts: Vec<String> = vec![];
-        let mut import_program_types = "".to_string();
-        let mut declare_programs = "".to_string();
-
-        self.idls.iter().for_each(|idl| {
-            let program_name = idl.name.to_case(Case::Snake);
-            let program_type = idl.name.to_case(Case::Pascal);
-            let program_import = format!(
-                r#"import {{ {} }} from "../target/types/{}";"#,
-                program_type, program_name
-            );
-            let program_definition = format!(
-                r#"const program = anchor.workspace.{} as Program<{}>;"#,
-                program_type, program_type
-            );
-            import_program_types = [import_program_types.clone(), program_import].join("\n");
-            declare_programs = [declare_programs.clone(), program_definition].join("\n");
-        });
-
-        let accounts_declarations: String = [
-            self.wallets
-                .values()
-                .map(|w| {
-                    accounts.push(w.to_key_value());
-                    w.to_mocha_account()
-                })
-                .collect::<Vec<String>>()
-                .join("\n"),
+impl LavaConfig {
+    fn declared_name(&self, reference: &str) -> Option<&String> {
+        let key = account_key(reference);
+        self.wallets
+            .keys()
+            .chain(self.mints.keys())
+            .chain(self.pdas.keys())
+            .chain(self.atas.keys())
+            .find(|n| account_key(n) == key)
+    }
+
+    /// The token program `ata` is under: its own if set, else its mint's.
+    pub(crate) fn token_program(&self, ata: &LavaATA) -> TokenProgram {
+        ata.token_program.unwrap_or_else(|| {
             self.mints
                 .values()
-                .map(|m| {
-                    accounts.push(m.to_key_value());
-                    m.to_mocha_account()
-                })
-                .collect::<Vec<String>>()
-                .join("\n"),
-            self.pdas
-                .values()
-                .map(|p| {
-                    accounts.push(p.to_key_value());
-                    p.to_mocha_account(&self.wallets)
-                })
-                .collect::<Vec<String>>()
-                .join("\n"),
-            self.atas
-                .values()
-                .map(|a| {
-                    accounts.push(a.to_key_value());
-                    if !self.wallets.contains_key(&a.authority) {
-                        a.to_mocha_account(true)
-                    } else {
-                        a.to_mocha_account(false)
-                    }
-                })
-                .collect::<Vec<String>>()
-                .join("\n"),
-        ]
-        .join("\n");
-
-        let import_spl = if self.tests.iter().any(|t| {
-            t.accounts
-                .as_object()
-                .unwrap()
+                .find(|m| account_key(&m.name) == account_key(&ata.mint))
+                .map(|m| m.token_program)
+                .unwrap_or_default()
+        })
+    }
+
+    /// The accounts that must be declared before `name` can be.
+    fn dependencies(&self, name: &str) -> Vec<&String> {
+        let seeds;
+        let references = if let Some(pda) = self.pdas.get(name) {
+            seeds = self.pda_seeds(pda);
+            seeds
                 .iter()
-                .any(|(k, v)| k == "tokenProgram")
-        }) {
-            r#"import {
-                ASSOCIATED_TOKEN_PROGRAM_ID,
-                MINT_SIZE,
-                TOKEN_PROGRAM_ID,
-                createAssociatedTokenAccountIdempotentInstruction,
-                createInitializeMint2Instruction,
-                createMintToInstruction,
-                getAssociatedTokenAddressSync,
-                getMinimumBalanceForRentExemptMint,
-                } from "@solana/spl-token";"#
+                .filter_map(|s| match s {
+                    LavaSeed::PublicKey(p) => Some(p.as_str()),
+                    LavaSeed::AccountField { account, .. } => Some(account.as_str()),
+                    _ => None,
+                })
+                .collect()
+        } else if let Some(ata) = self.atas.get(name) {
+            vec![ata.authority.as_str(), ata.mint.as_str()]
         } else {
-            ""
+            vec![]
         };
+        references
+            .into_iter()
+            .filter_map(|r| self.declared_name(r))
+            .collect()
+    }
 
-        let accounts_part = format!(
-            r#"{}
-        const accountsPublicKeys = {{{},
-            {}
-            systemProgram: SystemProgram.programId
-    }}"#,
-            accounts_declarations,
-            accounts.join(",\n"),
-            if import_spl.is_empty() {
-                "".to_string()
-            } else {
-                format!(
-                    r#"associatedTokenprogram: ASSOCIATED_TOKEN_PROGRAM_ID,
-            tokenProgram: TOKEN_PROGRAM_ID,"#
-                )
+    /// Wallets, mints, PDAs and ATAs in the order they are declared in the
+    /// generated test: each kind in source order, except that an account is
+    /// always moved after the accounts it is derived from.
+    pub(crate) fn declaration_order(&self) -> Vec<&String> {
+        fn visit<'a>(
+            config: &'a LavaConfig,
+            name: &'a String,
+            order: &mut Vec<&'a String>,
+            visiting: &mut Vec<&'a String>,
+        ) {
+            if order.contains(&name) || visiting.contains(&name) {
+                return;
             }
-        );
-
-        let user_defined_tests = self
-            .tests
-            .iter()
-            .map(|t| {
-                let idl = self.idls.iter().find(|i| i.name == t.programId).unwrap();
-                let instruction = idl
-                    .instructions
-                    .iter()
-                    .find(|i| i.name == t.instruction)
-                    .unwrap();
-                let signers = &instruction
-                    .accounts
-                    .iter()
-                    .filter(|a| a.isSigner)
-                    .collect::<Vec<&soda_sol::structs::InstructionAccount>>()
-                    .iter()
-                    .map(|a| a.name.clone())
-                    .collect::<Vec<String>>();
-
-                let signers_part = if !signers.is_empty() {
-                    format!("\n.signers([{}])", signers.join(", "))
-                } else {
-                    "".to_owned()
-                };
-                let binding = format!("{}", t.accounts).replace('"', "");
-                let mut accounts_to_chars = binding.chars();
-                accounts_to_chars.next();
-                accounts_to_chars.next_back();
-                let account_display = accounts_to_chars
-                    .collect::<String>()
-                    .split(',')
-                    .map(|pair| {
-                        let key_value = pair.split(':').collect::<Vec<&str>>();
-                        if key_value.len() > 1 {
-                            let key = key_value[0];
-                            let value = key_value[1];
-                            format!(
-                                r#"{}: accountsPublicKeys["{}"]"#,
-                                key,
-                                value.to_case(Case::Snake)
-                            )
-                        } else {
-                            "".to_string()
-                        }
-                    })
-                    .collect::<Vec<String>>()
-                    .join(", ");
-
-                let arguments = t
-                    .args
-                    .iter()
-                    .enumerate()
-                    .map(|(i, a)| match &instruction.args[i].kind {
-                        soda_sol::structs::InstructionType::U64 => {
-                            format!("new BN({})", format!("{}", a).replace('"', ""))
-                        }
-                        _ => "null".to_string(),
-                    })
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                let instructions = t.instruction.clone();
-                let name = t.name.clone();
-                format!(
-                    r#"it("{name}", async() => {{
-            const accounts = {{{account_display}}}
-            await program.methods
-            .{instructions}({arguments})
-            .accounts({{ ...accounts }}){signers_part}
-            .rpc()
-            .then(confirm)
-            .then(log);
-        }});"#
-                )
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
-
-        let wallets_with_sol = self.wallets.iter().filter(|(_, wallet)| wallet.balance > 0);
-        let setup_wallets = wallets_with_sol
-            .clone()
-            .map(|(_, wallet)| {
-                format!(
-                    r#"{{
-        pubkey: {}.publicKey,
-        lamports: {} * LAMPORTS_PER_SOL,
-      }}"#,
-                    wallet.name.to_case(Case::Snake),
-                    wallet.balance
-                )
-            })
-            .collect::<Vec<String>>()
-            .join(",\n");
-        let setup_mints = self
-            .mints
-            .values()
-            .map(|mint| {
-                format!(
-                    "SystemProgram.createAccount({{
-        fromPubkey: provider.publicKey,
-        newAccountPubkey: {}.publicKey,
-        lamports,
-        space: MINT_SIZE,
-        programId: TOKEN_PROGRAM_ID,
-      }})",
-                    mint.name.to_case(Case::Snake)
-                )
-            })
-            .collect::<Vec<String>>()
-            .join(",\n");
-
-        let rent_exception = if (!import_spl.is_empty()) {
-            "let lamports = await getMinimumBalanceForRentExemptMint(connection);"
-        } else {
-            ""
-        };
+            visiting.push(name);
+            for dependency in config.dependencies(name) {
+                visit(config, dependency, order, visiting);
+            }
+            visiting.pop();
+            order.push(name);
+        }
 
-        let mint_instructions = self.atas.iter().filter(|(_, ata)| ata.amount > 0 ).map(|(_, ata)| {
-            [format!(
-                r#"createInitializeMint2Instruction(
-        {}.publicKey,
-        {},
-        {}.publicKey,
-        null
-      )"#
-            , ata.mint.to_case(Case::Snake)
-                , self.mints.iter().find(|mint| mint.0 == &ata.mint).unwrap().1.decimals, ata.authority.to_case(Case::Snake)),
-            format!("createAssociatedTokenAccountIdempotentInstruction(provider.publicKey, {}, {}.publicKey, {}.publicKey)",ata.name.to_case(Case::Snake), ata.authority.to_case(Case::Snake), ata.mint.to_case(Case::Snake), ),
-            format!("createMintToInstruction({}.publicKey, {}, {}.publicKey, {})", ata.mint.to_case(Case::Snake), ata.name.to_case(Case::Snake), ata.authority.to_case(Case::Snake), ata.amount)
-                ].join(",\n")
-        }).collect::<Vec<String>>().join(",\n");
-        let setup = [setup_mints, mint_instructions].join(",\n");
-        let name = self.name.clone();
-        let instruction = [
-            self.mints
-                .values()
-                .map(|m| m.name.clone().to_case(Case::Snake))
-                .collect::<Vec<String>>()
-                .join(", "),
-            wallets_with_sol
-                .map(|(_, w)| w.name.clone().to_case(Case::Snake))
-                .collect::<Vec<String>>()
-                .join(", "),
-        ]
-        .iter()
-        .filter(|s| !s.is_empty())
-        .map(|s| format!("{}", s))
-        .collect::<Vec<String>>()
-        .join(", ");
-
-        format!(
-            r#"
-import * as anchor from "@coral-xyz/anchor";
-import {{ Program, BN }} from "@coral-xyz/anchor";
-import {{
-    Keypair,
-    LAMPORTS_PER_SOL,
-    PublicKey,
-    SystemProgram,
-    Transaction,
-    }} from "@solana/web3.js";
-{import_spl}
-{import_program_types}
-
-describe("{name}", () => {{
-    anchor.setProvider(anchor.AnchorProvider.env());
-
-    const provider = anchor.getProvider();
-
-    const connection = provider.connection;
-
-{declare_programs}
-
-    const confirm = async (signature: string): Promise<string> => {{
-        const block = await connection.getLatestBlockhash();
-        await connection.confirmTransaction({{
-            signature,
-            ...block,
-        }});
-    return signature;
-}};
-
-const log = async (signature: string): Promise<string> => {{
-    console.log(
-    `Your transaction signature: https://explorer.solana.com/transaction/${{signature}}?cluster=custom&customUrl=${{connection.rpcEndpoint}}`
-    );
-    return signature;
-}};
-
-// Accounts
-{accounts_part}
-
-it("setup", async() => {{
-    {rent_exception}
-    let tx = new Transaction();
-    [ {setup_wallets} ].forEach(account => {{
-        tx.add(SystemProgram.createAccount({{
-            fromPubkey: provider.publicKey,
-            newAccountPubkey: account.pubkey,
-            lamports: account.lamports,
-            space: 0,
-            programId: SystemProgram.programId,
-        }}));
-    }});
-    [ {setup} ].forEach(instruction => {{
-        tx.add(instruction);
-    }});
-    await provider.sendAndConfirm(tx, [{instruction}]).then(log);
-}})
-
-{user_defined_tests}
-}})"#
-        )
+        let mut order = vec![];
+        let mut visiting = vec![];
+        for name in self
+            .wallets
+            .keys()
+            .chain(self.mints.keys())
+            .chain(self.pdas.keys())
+            .chain(self.atas.keys())
+        {
+            visit(self, name, &mut order, &mut visiting);
+        }
+        order
     }
 }
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LavaConfig {
-    name: String,
-    wallets: HashMap<String, LavaWallet>,
-    mints: HashMap<String, LavaMint>,
-    atas: HashMap<String, LavaATA>,
-    programs: HashMap<String, LavaProgram>,
-    pdas: HashMap<String, LavaPDA>,
-    tests: Vec<LavaTest>,
-    idls: Vec<IDL>,
+    pub(crate) name: String,
+    pub(crate) wallets: IndexMap<String, LavaWallet>,
+    pub(crate) mints: IndexMap<String, LavaMint>,
+    pub(crate) atas: IndexMap<String, LavaATA>,
+    pub(crate) programs: IndexMap<String, LavaProgram>,
+    pub(crate) pdas: IndexMap<String, LavaPDA>,
+    pub(crate) tests: Vec<LavaTest>,
+    pub(crate) idls: Vec<Idl>,
+    /// Bytes `usize` and `isize` seeds are encoded as, which is 8 on SBF.
+    #[serde(default = "usize_width", rename = "usizeWidth")]
+    pub(crate) usize_width: u8,
 }
 
 impl TryFrom<&str> for LavaConfig {
@@ -362,23 +146,46 @@ impl TryFrom<&LavaConfigJSON> for LavaConfig {
     type Error = Error;
 
     fn try_from(value: &LavaConfigJSON) -> Result<Self, Error> {
-        let mut wallets = HashMap::new();
-        let mut programs = HashMap::new();
-        let mut pdas = HashMap::new();
-        let mut mints = HashMap::new();
-        let mut atas = HashMap::new();
-
-        for v in &value.accounts {
-            let name = v
-                .get("name")
-                .and_then(|n| n.as_str())
-                .ok_or(Error::msg("Account name missing"))?;
-
-            match v
-                .get("kind")
-                .and_then(|k| k.as_str())
-                .ok_or(Error::msg("Account kind missing"))?
-            {
+        let mut wallets = IndexMap::new();
+        let mut programs = IndexMap::new();
+        let mut pdas = IndexMap::new();
+        let mut mints = IndexMap::new();
+        let mut atas = IndexMap::new();
+        let mut errors = LavaError::default();
+        // Accounts are keyed by name, so a second one with the same name
+        // would replace the first without `check` ever seeing it, and
+        // `check` no longer knows which of two clashing names came last.
+        let mut names: Vec<(&str, usize)> = vec![];
+
+        for (i, v) in value.accounts.iter().enumerate() {
+            let Some(name) = v.get("name").and_then(|n| n.as_str()) else {
+                errors.push(
+                    LavaErrorKind::InvalidSchema,
+                    format!("accounts.{}", i),
+                    format!("account {} has no name", i),
+                );
+                continue;
+            };
+            let key = account_key(name);
+            if let Some((first, j)) = names.iter().find(|(n, _)| account_key(n) == key) {
+                errors.push(
+                    LavaErrorKind::DuplicateName,
+                    format!("accounts.{}", name),
+                    if *first == name {
+                        format!("accounts {} and {} are both named \"{}\"", j, i, name)
+                    } else {
+                        format!(
+                            "accounts \"{}\", \"{}\" all generate the identifier \"{}\"",
+                            first, name, key
+                        )
+                    },
+                );
+                continue;
+            }
+            names.push((name, i));
+
+            let kind = v.get("kind").and_then(|k| k.as_str()).unwrap_or_default();
+            match kind {
                 "program" => {
                     let program: LavaProgram = serde_json::from_value(v.clone())?;
                     programs.insert(name.to_string(), program);
@@ -399,14 +206,22 @@ impl TryFrom<&LavaConfigJSON> for LavaConfig {
                     let ata: LavaATA = serde_json::from_value(v.clone())?;
                     atas.insert(name.to_string(), ata);
                 }
-                _ => {
-                    println!("Not implemented: {}", v.get("kind").unwrap());
-                }
+                _ => errors.push(
+                    LavaErrorKind::UnknownKind,
+                    format!("accounts.{}.kind", name),
+                    match kind {
+                        "" => format!("account \"{}\" has no kind", name),
+                        _ => format!(
+                            "account \"{}\" is a \"{}\", which is not one of wallet, mint, ata, pda or program",
+                            name, kind
+                        ),
+                    },
+                ),
             }
         }
         let tests = value.tests.clone();
-        let idls: Vec<IDL> = value.idls.clone();
-        Ok(LavaConfig {
+        let idls: Vec<Idl> = value.idls.clone();
+        let config = LavaConfig {
             name: value.name.clone(),
             wallets,
             mints,
@@ -415,80 +230,150 @@ impl TryFrom<&LavaConfigJSON> for LavaConfig {
             pdas,
             tests,
             idls,
-        })
+            usize_width: value.usize_width,
+        };
+        if let Err(e) = config.check() {
+            errors.extend(e);
+        }
+        errors.into_result()?;
+        Ok(config)
     }
 }
 
 #[wasm_bindgen]
 impl LavaConfig {
     #[wasm_bindgen(constructor)]
-    pub fn new(lava_Config: Option<String>) -> Result<LavaConfig, JsError> {
-        match lava_Config {
-            Some(config) => match LavaConfig::try_from(config.as_str()) {
-                Ok(config) => Ok(config),
-                Err(e) => Err(JsError::new(&e.to_string())),
-            },
+    pub fn new(lava_config: Option<String>) -> Result<LavaConfig, LavaError> {
+        match lava_config {
+            Some(config) => Ok(LavaConfig::try_from(config.as_str())?),
             None => Ok(LavaConfig {
                 name: "Lava".to_string(),
-                wallets: HashMap::new(),
-                mints: HashMap::new(),
-                atas: HashMap::new(),
-                programs: HashMap::new(),
-                pdas: HashMap::new(),
+                wallets: IndexMap::new(),
+                mints: IndexMap::new(),
+                atas: IndexMap::new(),
+                programs: IndexMap::new(),
+                pdas: IndexMap::new(),
                 tests: vec![],
                 idls: vec![],
+                usize_width: DEFAULT_USIZE_WIDTH,
             }),
         }
     }
 }
 
+/// Version written into workspaces exported from a [`LavaConfig`].
+pub(crate) const LAVA_JSON_VERSION: &str = "0.0.0";
+
+impl From<&LavaConfig> for LavaConfigJSON {
+    fn from(value: &LavaConfig) -> Self {
+        fn tagged<T: Serialize>(account: &T, kind: &str) -> Value {
+            let mut v = serde_json::to_value(account).unwrap_or_default();
+            if let Some(o) = v.as_object_mut() {
+                o.insert("kind".to_string(), Value::from(kind));
+            }
+            v
+        }
+
+        let accounts = value
+            .wallets
+            .values()
+            .map(|w| tagged(w, "wallet"))
+            .chain(value.mints.values().map(|m| tagged(m, "mint")))
+            .chain(value.programs.values().map(|p| tagged(p, "program")))
+            .chain(value.atas.values().map(|a| tagged(a, "ata")))
+            .chain(value.pdas.values().map(|p| tagged(p, "pda")))
+            .collect();
+        LavaConfigJSON {
+            name: value.name.clone(),
+            accounts,
+            tests: value.tests.clone(),
+            idls: value.idls.clone(),
+            usize_width: value.usize_width,
+            version: LAVA_JSON_VERSION.to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LavaConfigJSON {
-    name: String,
-    accounts: Vec<Value>,
-    tests: Vec<LavaTest>,
-    idls: Vec<IDL>,
-    version: String,
+    pub(crate) name: String,
+    pub(crate) accounts: Vec<Value>,
+    pub(crate) tests: Vec<LavaTest>,
+    pub(crate) idls: Vec<Idl>,
+    #[serde(
+        default = "usize_width",
+        rename = "usizeWidth",
+        skip_serializing_if = "is_default_usize_width"
+    )]
+    pub(crate) usize_width: u8,
+    pub(crate) version: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LavaTest {
-    name: String,
-    programId: String,
-    instruction: String,
-    accounts: Value,
-    args: Vec<Value>,
+    pub(crate) name: String,
+    #[serde(rename = "programId")]
+    pub(crate) program_id: String,
+    pub(crate) instruction: String,
+    pub(crate) accounts: Value,
+    pub(crate) args: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) expect: Option<LavaExpect>,
+    #[serde(default, rename = "assert", skip_serializing_if = "Vec::is_empty")]
+    pub(crate) asserts: Vec<LavaAssert>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) deltas: Vec<LavaDelta>,
 }
 
+/// A program account. `address` is `"program"` for programs the Anchor
+/// workspace builds, `"idl"` to use the IDL's `metadata.address`, or a base58
+/// address for programs deployed elsewhere.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LavaProgram {
-    name: String,
+    pub(crate) name: String,
     #[serde(default = "anchor_program")]
-    address: String,
+    pub(crate) address: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct LavaWallet {
-    name: String,
-    balance: u64,
+/// Where generated tests load a program from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgramSource {
+    /// `anchor.workspace`, for programs built by the project under test.
+    Workspace,
+    /// A fixed base58 address, e.g. a program cloned into the local validator.
+    Address(String),
 }
 
-impl LavaWallet {
-    fn to_mocha_account(&self) -> String {
-        format!(
-            "const {} = Keypair.generate();",
-            self.name.to_case(Case::Snake)
-        )
+pub(crate) fn parse_address(address: &str) -> Result<String, String> {
+    match bs58::decode(address).into_vec() {
+        Ok(bytes) if bytes.len() == 32 => Ok(address.to_string()),
+        _ => Err(format!(
+            "\"{}\" is not a base58 encoded 32 byte address",
+            address
+        )),
     }
-    fn to_key_value(&self) -> String {
-        format!(
-            "{}: {}.publicKey",
-            self.name.to_case(Case::Snake),
-            self.name.to_case(Case::Snake)
-        )
+}
+
+impl LavaConfig {
+    pub(crate) fn program_source(&self, idl: &Idl) -> Result<ProgramSource, String> {
+        let program = self
+            .programs
+            .values()
+            .find(|p| account_key(&p.name) == account_key(&idl.name));
+        match program.map(|p| p.address.as_str()) {
+            None | Some("program") => Ok(ProgramSource::Workspace),
+            Some("idl") => parse_address(&idl.metadata.address).map(ProgramSource::Address),
+            Some(address) => parse_address(address).map(ProgramSource::Address),
+        }
     }
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LavaWallet {
+    pub(crate) name: String,
+    pub(crate) balance: u64,
+}
+
 impl Default for LavaWallet {
     fn default() -> Self {
         LavaWallet {
@@ -500,169 +385,91 @@ impl Default for LavaWallet {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LavaMint {
-    name: String,
-    symbol: String,
-    decimals: u8,
+    pub(crate) name: String,
+    pub(crate) symbol: String,
+    pub(crate) decimals: u8,
+    /// The wallet, PDA or address that mints the token, the provider unless
+    /// named.
     #[serde(default = "anchor_provider")]
-    mint_authority: String,
-    freeze_authority: Option<LavaWallet>,
+    pub(crate) mint_authority: String,
+    #[serde(
+        default,
+        deserialize_with = "account_name",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) freeze_authority: Option<String>,
+    #[serde(
+        default,
+        rename = "tokenProgram",
+        skip_serializing_if = "TokenProgram::is_token"
+    )]
+    pub(crate) token_program: TokenProgram,
+    /// Token-2022 extensions, initialized in the order Token-2022 requires
+    /// whatever order they are listed in.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) extensions: Vec<LavaExtension>,
 }
 
-impl LavaMint {
-    fn to_mocha_account(&self) -> String {
-        format!(
-            "const {} = Keypair.generate();",
-            self.name.to_case(Case::Snake)
-        )
-    }
-    fn to_key_value(&self) -> String {
-        format!(
-            "{}: {}.publicKey",
-            self.name.to_case(Case::Snake),
-            self.name.to_case(Case::Snake)
-        )
-    }
+fn usize_width() -> u8 {
+    DEFAULT_USIZE_WIDTH
+}
+
+fn is_default_usize_width(width: &u8) -> bool {
+    *width == DEFAULT_USIZE_WIDTH
 }
 
 fn anchor_provider() -> String {
     "provider".to_string()
 }
 
+/// Reads an account reference, also taking the `{ "name": .. }` objects
+/// older workspaces wrote for freeze authorities.
+fn account_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Name {
+        Name(String),
+        Account { name: String },
+    }
+    Ok(Option::<Name>::deserialize(deserializer)?.map(|n| match n {
+        Name::Name(name) | Name::Account { name } => name,
+    }))
+}
+
 fn anchor_program() -> String {
     "program".to_string()
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LavaATA {
-    name: String,
-    authority: String,
-    mint: String,
-    amount: u64,
+    pub(crate) name: String,
+    pub(crate) authority: String,
+    pub(crate) mint: String,
+    pub(crate) amount: u64,
     #[serde(default)]
-    init: bool,
-}
-
-impl LavaATA {
-    fn to_mocha_account(&self, pda_owner: bool) -> String {
-        if pda_owner {
-            format!(
-                "const {} = getAssociatedTokenAddressSync({}.publicKey, {}, true);",
-                self.name.to_case(Case::Snake),
-                self.mint.to_case(Case::Snake),
-                self.authority.to_case(Case::Snake)
-            )
-        } else {
-            format!(
-                "const {} = getAssociatedTokenAddressSync({}.publicKey, {}.publicKey);",
-                self.name.to_case(Case::Snake),
-                self.mint.to_case(Case::Snake),
-                self.authority.to_case(Case::Snake)
-            )
-        }
-    }
-
-    fn to_key_value(&self) -> String {
-        self.name.to_case(Case::Snake)
-    }
+    pub(crate) init: bool,
+    /// Defaults to the token program of the mint.
+    #[serde(
+        default,
+        rename = "tokenProgram",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) token_program: Option<TokenProgram>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LavaPDA {
-    name: String,
-    program: String,
-    seeds: Vec<LavaSeed>,
-}
-
-#[derive(Deserialize, Debug)]
-struct LavaSeedJSON {
-    kind: String,
-    value: Value,
-}
-
-#[derive(Deserialize)]
-pub struct LavaPDAJSON {
-    name: String,
-    program: String,
-    seeds: Vec<LavaSeedJSON>,
+    pub(crate) name: String,
+    pub(crate) program: String,
+    /// Left out to derive the PDA from the IDL `pda` metadata of the
+    /// instruction accounts tests map it to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) seeds: Vec<LavaSeed>,
 }
 
 impl LavaPDA {
     pub fn from_json(v: &[u8]) -> Result<Self, Error> {
-        let lss: LavaPDAJSON =
-            serde_json::from_slice(v).map_err(|_| Error::msg("Invalid PDA schema"))?;
-        let seeds = lss
-            .seeds
-            .iter()
-            .map(|s| match s.kind.as_str() {
-                "u8" => Ok(LavaSeed::U8(
-                    s.value.as_u64().ok_or(Error::msg("Invalid u8"))? as u8,
-                )),
-                "i8" => Ok(LavaSeed::I8(
-                    s.value.as_i64().ok_or(Error::msg("Invalid i8"))? as i8,
-                )),
-                "u16" => Ok(LavaSeed::U16(
-                    s.value.as_u64().ok_or(Error::msg("Invalid u16"))? as u16,
-                )),
-                "i16" => Ok(LavaSeed::I16(
-                    s.value.as_i64().ok_or(Error::msg("Invalid i16"))? as i16,
-                )),
-                "u32" => Ok(LavaSeed::U32(
-                    s.value.as_u64().ok_or(Error::msg("Invalid u32"))? as u32,
-                )),
-                "i32" => Ok(LavaSeed::I32(
-                    s.value.as_i64().ok_or(Error::msg("Invalid i32"))? as i32,
-                )),
-                "u64" => Ok(LavaSeed::U64(
-                    s.value.as_u64().ok_or(Error::msg("Invalid u64"))?,
-                )),
-                "i64" => Ok(LavaSeed::I64(
-                    s.value.as_i64().ok_or(Error::msg("Invalid i64"))?,
-                )),
-                "String" => Ok(LavaSeed::String(
-                    s.value
-                        .as_str()
-                        .ok_or(Error::msg("Invalid String"))?
-                        .to_string(),
-                )),
-                "Pubkey" => Ok(LavaSeed::PublicKey(
-                    s.value
-                        .as_str()
-                        .ok_or(Error::msg("Invalid Public Key"))?
-                        .to_string(),
-                )),
-                _ => Err(Error::msg("Unsupported PDA seed type")),
-            })
-            .collect::<Result<Vec<LavaSeed>, Error>>()?;
-
-        Ok(LavaPDA {
-            name: lss.name,
-            program: lss.program,
-            seeds,
-        })
-    }
-
-    pub fn to_mocha_account(&self, wallets: &HashMap<String, LavaWallet>) -> String {
-        format!(
-            "const {} = PublicKey.findProgramAddressSync([{}], {})[0]",
-            self.name.to_case(Case::Snake),
-            self.seeds
-                .iter()
-                .map(|s| {
-                    if let LavaSeed::PublicKey(p) = s {
-                        if wallets.contains_key(p) {
-                            return s.to_mocha_account(true);
-                        }
-                    };
-                    s.to_mocha_account(false)
-                })
-                .collect::<Vec<String>>()
-                .join(", "),
-            "program.programId"
-        )
-    }
-
-    fn to_key_value(&self) -> String {
-        self.name.to_case(Case::Snake)
+        serde_json::from_slice(v).map_err(|e| Error::msg(format!("Invalid PDA schema: {}", e)))
     }
 }
 
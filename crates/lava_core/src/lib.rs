@@ -1,33 +1,121 @@
+pub use codegen::{TestGenerator, TestPlan};
+pub use error::{LavaError, LavaErrorKind, LavaIssue};
+pub use idl::{Idl, IdlFormat};
 pub use lava_config::{LavaConfig, LavaConfigJSON};
 use wasm_bindgen::prelude::*;
 
+mod args;
+pub mod asserts;
+pub mod builtins;
+mod check;
+pub mod codegen;
+pub mod error;
+pub mod expect;
+#[cfg(test)]
+mod fixtures;
+pub mod idl;
 pub mod lava_config;
+mod scaffold;
 pub mod seeds;
+pub mod token;
 
 #[wasm_bindgen]
-pub fn json_to_mocka(json: &str) -> String {
-    let config: LavaConfigJSON = serde_json::from_str(json).unwrap();
-    let config = LavaConfig::try_from(&config).unwrap();
-    config.to_mocha()
+pub fn json_to_mocka(json: &str) -> Result<String, LavaError> {
+    let config: LavaConfigJSON = serde_json::from_str(json)?;
+    let config = LavaConfig::try_from(&config)?;
+    config.try_to_mocha()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{LavaConfig, LavaConfigJSON};
-    use std::fs::File;
-    use std::io::{Read, Write};
+    use crate::fixtures;
+    use crate::{json_to_mocka, LavaErrorKind};
 
+    /// Set `LAVA_UPDATE_SNAPSHOTS` to rewrite the snapshot after an
+    /// intended change to the generated tests.
     #[test]
     fn test_parse() {
-        let file_name = "../../workspaces_examples/Escrow2024-Make.json";
-        let mut file = File::open(file_name).unwrap();
-        let mut buffer = String::new();
-        file.read_to_string(&mut buffer).unwrap();
-        let lava_config_json: LavaConfigJSON = serde_json::from_slice(buffer.as_bytes()).unwrap();
-        let config = LavaConfig::try_from(&lava_config_json).unwrap();
-        let mocha = config.to_mocha();
-        let mut tests = File::create("../../test.mocha.ts").unwrap();
-        tests.write_all(mocha.as_bytes()).unwrap();
-        //   println!("{}", mocha);
+        let mocha = fixtures::config(&fixtures::escrow_make())
+            .to_mocha()
+            .unwrap();
+        let snapshot = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/snapshots/escrow_make.mocha.ts"
+        );
+        if std::env::var_os("LAVA_UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(snapshot, &mocha).unwrap();
+        }
+        assert_eq!(mocha, std::fs::read_to_string(snapshot).unwrap());
+    }
+
+    #[test]
+    fn test_output_is_deterministic() {
+        let example = fixtures::escrow().to_string();
+        let mocha = json_to_mocka(&example).unwrap();
+        for _ in 0..8 {
+            assert_eq!(json_to_mocka(&example).unwrap(), mocha);
+        }
+
+        let declared = [
+            "const maker ",
+            "const token_a ",
+            "const escrow ",
+            "const vault ",
+        ]
+        .map(|d| mocha.find(d).unwrap());
+        assert!(declared.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_multiple_programs() {
+        let mut escrow = fixtures::escrow_make();
+        let mut dice = fixtures::dice();
+        // Both examples declare a "vault".
+        dice["accounts"][3]["name"] = "house vault".into();
+        dice["tests"][0]["accounts"]["vault"] = "house vault".into();
+        for section in ["accounts", "tests", "idls"] {
+            let dice = dice[section].as_array().unwrap().clone();
+            escrow[section].as_array_mut().unwrap().extend(dice);
+        }
+
+        let mocha = json_to_mocka(&escrow.to_string()).unwrap();
+        assert!(mocha.contains("const anchor_escrow_program = anchor.workspace.AnchorEscrow"));
+        assert!(mocha.contains("const soldice_anchor_program = anchor.workspace.SoldiceAnchor"));
+        assert!(mocha.contains("maker.publicKey.toBuffer(), new BN(1).toBuffer(\"le\", 8)], anchor_escrow_program.programId)"));
+        assert!(mocha.contains("house.publicKey.toBuffer()], soldice_anchor_program.programId)"));
+        assert!(mocha.contains("await anchor_escrow_program.methods\n            .make("));
+        assert!(mocha.contains("await soldice_anchor_program.methods\n            .initialize("));
+    }
+
+    #[test]
+    fn test_fixed_address_programs() {
+        let mut dice = fixtures::dice();
+        dice["accounts"][1]["address"] = "idl".into();
+        let mocha = json_to_mocka(&dice.to_string()).unwrap();
+        assert!(!mocha.contains("../target/types/soldice_anchor"));
+        assert!(mocha.contains(
+            r#"const soldice_anchor_address = new PublicKey("BVDbBunXTymnR7W2HcnwMtS34WJATAHHsry6s7eTV6ie");"#
+        ));
+        assert!(mocha.contains(
+            "const soldice_anchor_program = new Program(soldice_anchor_idl, soldice_anchor_address, provider);"
+        ));
+
+        dice["accounts"][1]["address"] = "BVDbBunXTymnR7W2Hcnw".into();
+        let err = json_to_mocka(&dice.to_string()).unwrap_err();
+        assert_eq!(err.issues()[0].kind, LavaErrorKind::InvalidAddress);
+        assert_eq!(err.issues()[0].path, "accounts.soldice_anchor.address");
+    }
+
+    #[test]
+    fn test_json_to_mocka_reports_typos() {
+        let mut workspace = fixtures::escrow_make();
+        workspace["tests"][0]["programId"] = "anchor_escrw".into();
+
+        let err = json_to_mocka(&workspace.to_string()).unwrap_err();
+        assert_eq!(err.issues()[0].kind, LavaErrorKind::UnknownProgram);
+        assert_eq!(err.issues()[0].path, "tests.Make.programId");
+
+        let err = json_to_mocka("{ \"name\": ").unwrap_err();
+        assert_eq!(err.issues()[0].kind, LavaErrorKind::InvalidSchema);
     }
 }
@@ -0,0 +1,202 @@
+use convert_case::{Case, Casing};
+use indexmap::IndexMap;
+use serde_json::{json, Map, Value};
+use soda_sol::structs::InstructionType;
+
+use crate::args::{defined_type, DefinedType};
+use crate::builtins::builtin;
+use crate::idl::Idl;
+use crate::lava_config::{LavaConfigJSON, LavaTest, LAVA_JSON_VERSION};
+use crate::seeds::DEFAULT_USIZE_WIDTH;
+
+/// Balance given to scaffolded wallets, in SOL.
+const WALLET_BALANCE: u64 = 10;
+
+/// A value of `kind` for a scaffolded test to start from.
+fn placeholder(idl: &Idl, kind: &InstructionType) -> Value {
+    match kind {
+        InstructionType::U8
+        | InstructionType::U16
+        | InstructionType::U32
+        | InstructionType::I8
+        | InstructionType::I16
+        | InstructionType::I32 => json!(0),
+        InstructionType::U64
+        | InstructionType::I64
+        | InstructionType::U128
+        | InstructionType::I128 => json!("0"),
+        InstructionType::Bool => json!(false),
+        InstructionType::String => json!(""),
+        InstructionType::PublicKey => json!("11111111111111111111111111111111"),
+        InstructionType::Option(_) => Value::Null,
+        InstructionType::Array(inner, len) => json!(vec![placeholder(idl, inner); *len]),
+        InstructionType::Tuple(kinds) => {
+            Value::Array(kinds.iter().map(|k| placeholder(idl, k)).collect())
+        }
+        InstructionType::Bytes
+        | InstructionType::Vec(_)
+        | InstructionType::HashSet(_)
+        | InstructionType::BTreeSet(_)
+        | InstructionType::HashMap(_, _)
+        | InstructionType::BTreeMap(_, _) => json!([]),
+        InstructionType::Defined(name) => match defined_type(idl, name) {
+            Ok(DefinedType::Struct(fields)) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(field, kind)| (field.to_case(Case::Camel), placeholder(idl, kind)))
+                    .collect::<Map<String, Value>>(),
+            ),
+            Ok(DefinedType::Enum(variants)) => {
+                variants.first().map(|v| json!(v)).unwrap_or_default()
+            }
+            Err(_) => Value::Null,
+        },
+    }
+}
+
+impl LavaConfigJSON {
+    /// A starter workspace for the programs described by `idls`.
+    ///
+    /// Every IDL gets a program account, every signer a wallet, every account
+    /// with `pda.seeds` metadata a PDA derived from them, and every
+    /// instruction a test with
+    /// placeholder args. Accounts the IDL can't describe, like mints and
+    /// token accounts, are mapped by name but left for the user to declare,
+    /// so `lava validate` lists exactly what is missing. Builtins and
+    /// accounts the IDL pins to an address are filled in when tests run.
+    pub fn from_idls(name: &str, idls: Vec<Idl>) -> LavaConfigJSON {
+        let mut accounts: IndexMap<String, Value> = IndexMap::new();
+        let mut tests = vec![];
+
+        for idl in &idls {
+            accounts.insert(
+                idl.name.clone(),
+                json!({ "name": idl.name, "kind": "program" }),
+            );
+        }
+
+        for idl in &idls {
+            let raw = idl.json();
+            for (i, instruction) in idl.instructions.iter().enumerate() {
+                for (j, account) in instruction.accounts.iter().enumerate() {
+                    if accounts.contains_key(&account.name) {
+                        continue;
+                    }
+                    let pda = &raw["instructions"][i]["accounts"][j]["pda"];
+                    if pda["seeds"].as_array().is_some_and(|s| !s.is_empty())
+                        && pda["programId"].is_null()
+                    {
+                        // Seeds are read from the IDL when the workspace is loaded.
+                        accounts.insert(
+                            account.name.clone(),
+                            json!({ "name": account.name, "kind": "pda", "program": idl.name }),
+                        );
+                    } else if account.isSigner {
+                        accounts.insert(
+                            account.name.clone(),
+                            json!({
+                                "name": account.name,
+                                "kind": "wallet",
+                                "balance": WALLET_BALANCE,
+                            }),
+                        );
+                    }
+                }
+
+                tests.push(LavaTest {
+                    name: instruction.name.to_case(Case::Title),
+                    program_id: idl.name.clone(),
+                    instruction: instruction.name.clone(),
+                    accounts: Value::Object(
+                        instruction
+                            .accounts
+                            .iter()
+                            .filter(|a| {
+                                builtin(&a.name).is_none()
+                                    && idl.fixed_address(&instruction.name, &a.name).is_none()
+                            })
+                            .map(|a| (a.name.clone(), json!(a.name)))
+                            .collect(),
+                    ),
+                    args: instruction
+                        .args
+                        .iter()
+                        .map(|a| placeholder(idl, &a.kind))
+                        .collect(),
+                    expect: None,
+                    asserts: vec![],
+                    deltas: vec![],
+                });
+            }
+        }
+
+        LavaConfigJSON {
+            name: name.to_string(),
+            accounts: accounts.into_values().collect(),
+            tests,
+            idls,
+            usize_width: DEFAULT_USIZE_WIDTH,
+            version: LAVA_JSON_VERSION.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use crate::idl::Idl;
+    use serde_json::json;
+
+    use crate::{LavaConfig, LavaConfigJSON, LavaErrorKind};
+
+    fn escrow_idl() -> Idl {
+        let mut idl = fixtures::escrow_make()["idls"][0].clone();
+        idl["instructions"][0]["accounts"][1]["pda"] = json!({ "seeds": [
+            { "kind": "const", "type": "string", "value": "escrow" },
+            { "kind": "account", "type": "publicKey", "path": "maker" },
+            { "kind": "arg", "type": "u64", "path": "seed" }
+        ] });
+        serde_json::from_value(idl).unwrap()
+    }
+
+    #[test]
+    fn scaffolds_accounts_and_tests() {
+        let json = LavaConfigJSON::from_idls("Escrow", vec![escrow_idl()]);
+        let accounts = serde_json::to_value(&json.accounts).unwrap();
+        assert_eq!(
+            accounts,
+            json!([
+                { "name": "anchor_escrow", "kind": "program" },
+                { "name": "maker", "kind": "wallet", "balance": 10 },
+                { "name": "escrow", "kind": "pda", "program": "anchor_escrow" },
+                { "name": "taker", "kind": "wallet", "balance": 10 }
+            ])
+        );
+        assert_eq!(
+            json.tests.iter().map(|t| &t.name).collect::<Vec<_>>(),
+            ["Make", "Refund", "Take"]
+        );
+        assert_eq!(json.tests[0].args, [json!("0"), json!("0"), json!("0")]);
+
+        // Only the token accounts the IDL can't describe are left to declare.
+        let err = LavaConfig::try_from(&json)
+            .unwrap_err()
+            .downcast::<crate::LavaError>()
+            .unwrap();
+        assert!(err
+            .issues()
+            .iter()
+            .all(|i| i.kind == LavaErrorKind::UnknownAccount
+                && [
+                    "mintA",
+                    "mintB",
+                    "makerAtaA",
+                    "makerAtaB",
+                    "takerAtaA",
+                    "takerAtaB",
+                    "vault"
+                ]
+                .iter()
+                .any(|a| i.path.ends_with(&format!(".{}", a)))));
+    }
+}
@@ -1,7 +1,27 @@
+use anyhow::Error;
 use convert_case::{Case, Casing};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use soda_sol::structs::InstructionType;
 
+use crate::args::{defined_type, hex_bytes, integer, DefinedType};
+use crate::check::account_key;
+use crate::codegen::{AccountRef, ArgValue, Seed};
+use crate::error::LavaErrorKind;
+use crate::lava_config::{LavaConfig, LavaPDA, LavaTest};
+
+/// Bytes `usize` and `isize` seeds take on the SBF target programs run on.
+pub(crate) const DEFAULT_USIZE_WIDTH: u8 = 8;
+
+/// A PDA seed, written as `{ "kind": "u64", "value": 1 }` in workspaces.
+///
+/// `bytes` and `[u8;32]` seeds take either an array of bytes, a `0x`
+/// prefixed hex string or a base58 string. Seeds can also take their value
+/// from a test, `{ "kind": "arg", "test": "Make", "arg": "seed" }`, or from
+/// another account, `{ "kind": "accountField", "account": "Escrow",
+/// "field": "maker" }`.
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "Value", into = "Value")]
 pub enum LavaSeed {
     String(String),
     U8(u8),
@@ -12,33 +32,800 @@ pub enum LavaSeed {
     I32(i32),
     U64(u64),
     I64(i64),
-    USize(usize),
-    ISize(isize),
+    U128(u128),
+    I128(i128),
+    /// Encoded as many bytes as the workspace's `usizeWidth`.
+    USize(u64),
+    ISize(i64),
+    Bool(bool),
+    Bytes(Vec<u8>),
+    /// Exactly 32 bytes, e.g. a hash.
+    Hash([u8; 32]),
     PublicKey(String),
+    /// Whatever test `test` passes as its instruction arg `arg`.
+    Arg {
+        test: String,
+        arg: String,
+    },
+    /// Field `field` of the program account stored at the PDA `account`,
+    /// decoded as `account_type`, or as the type named like the account.
+    AccountField {
+        account: String,
+        field: String,
+        account_type: Option<String>,
+    },
+}
+
+fn number<T: std::str::FromStr>(value: &Value, kind: &str) -> Result<T, Error> {
+    let bits = kind[1..].parse().unwrap_or(64);
+    integer(value, kind.starts_with('i'), bits)
+        .map_err(Error::msg)?
+        .parse()
+        .map_err(|_| Error::msg(format!("Invalid {}", kind)))
+}
+
+fn bytes(value: &Value) -> Result<Vec<u8>, Error> {
+    match value {
+        Value::String(s) if s.starts_with("0x") => hex_bytes(s).map_err(Error::msg),
+        Value::String(s) => bs58::decode(s)
+            .into_vec()
+            .map_err(|_| Error::msg(format!("\"{}\" is neither 0x hex nor base58", s))),
+        Value::Array(items) => items.iter().map(|b| number::<u8>(b, "u8")).collect(),
+        _ => Err(Error::msg(format!("Invalid bytes {}", value))),
+    }
+}
+
+/// Seeds as they were stored before they took the `{ kind, value }` form,
+/// e.g. `{ "U64": 1 }`.
+#[derive(Deserialize)]
+enum LegacySeed {
+    String(String),
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    USize(u64),
+    ISize(i64),
+    PublicKey(String),
+}
+
+impl From<LegacySeed> for LavaSeed {
+    fn from(seed: LegacySeed) -> Self {
+        match seed {
+            LegacySeed::String(s) => LavaSeed::String(s),
+            LegacySeed::U8(n) => LavaSeed::U8(n),
+            LegacySeed::I8(n) => LavaSeed::I8(n),
+            LegacySeed::U16(n) => LavaSeed::U16(n),
+            LegacySeed::I16(n) => LavaSeed::I16(n),
+            LegacySeed::U32(n) => LavaSeed::U32(n),
+            LegacySeed::I32(n) => LavaSeed::I32(n),
+            LegacySeed::U64(n) => LavaSeed::U64(n),
+            LegacySeed::I64(n) => LavaSeed::I64(n),
+            LegacySeed::USize(n) => LavaSeed::USize(n),
+            LegacySeed::ISize(n) => LavaSeed::ISize(n),
+            LegacySeed::PublicKey(p) => LavaSeed::PublicKey(p),
+        }
+    }
+}
+
+impl TryFrom<Value> for LavaSeed {
+    type Error = Error;
+
+    fn try_from(s: Value) -> Result<Self, Error> {
+        if s.get("kind").is_none() {
+            if let Ok(legacy) = serde_json::from_value::<LegacySeed>(s.clone()) {
+                return Ok(legacy.into());
+            }
+        }
+        let value = &s["value"];
+        let kind = s["kind"].as_str().unwrap_or_default();
+        let text = |key: &str| {
+            s[key]
+                .as_str()
+                .map(|v| v.to_string())
+                .ok_or(Error::msg(format!("{} seeds need a \"{}\"", kind, key)))
+        };
+        match kind {
+            "u8" => Ok(LavaSeed::U8(number(value, kind)?)),
+            "i8" => Ok(LavaSeed::I8(number(value, kind)?)),
+            "u16" => Ok(LavaSeed::U16(number(value, kind)?)),
+            "i16" => Ok(LavaSeed::I16(number(value, kind)?)),
+            "u32" => Ok(LavaSeed::U32(number(value, kind)?)),
+            "i32" => Ok(LavaSeed::I32(number(value, kind)?)),
+            "u64" => Ok(LavaSeed::U64(number(value, kind)?)),
+            "i64" => Ok(LavaSeed::I64(number(value, kind)?)),
+            "u128" => Ok(LavaSeed::U128(number(value, kind)?)),
+            "i128" => Ok(LavaSeed::I128(number(value, kind)?)),
+            "usize" => Ok(LavaSeed::USize(number(value, "u64")?)),
+            "isize" => Ok(LavaSeed::ISize(number(value, "i64")?)),
+            "bool" => Ok(LavaSeed::Bool(
+                value.as_bool().ok_or(Error::msg("Invalid bool"))?,
+            )),
+            "bytes" => Ok(LavaSeed::Bytes(bytes(value)?)),
+            "[u8;32]" | "[u8; 32]" => {
+                Ok(LavaSeed::Hash(bytes(value)?.try_into().map_err(|_| {
+                    Error::msg("Invalid [u8;32], expected 32 bytes")
+                })?))
+            }
+            "String" => Ok(LavaSeed::String(
+                value
+                    .as_str()
+                    .ok_or(Error::msg("Invalid String"))?
+                    .to_string(),
+            )),
+            "Pubkey" => Ok(LavaSeed::PublicKey(
+                value
+                    .as_str()
+                    .ok_or(Error::msg("Invalid Public Key"))?
+                    .to_string(),
+            )),
+            "arg" => Ok(LavaSeed::Arg {
+                test: text("test")?,
+                arg: text("arg")?,
+            }),
+            "accountField" => Ok(LavaSeed::AccountField {
+                account: text("account")?,
+                field: text("field")?,
+                account_type: s["type"].as_str().map(|t| t.to_string()),
+            }),
+            _ => Err(Error::msg("Unsupported PDA seed type")),
+        }
+    }
+}
+
+impl From<LavaSeed> for Value {
+    fn from(seed: LavaSeed) -> Self {
+        seed.to_json()
+    }
 }
 
 impl LavaSeed {
-    pub fn to_mocha_account(&self, pda_owner: bool) -> String {
+    /// The `{ kind, value }` form seeds take in workspaces.
+    pub fn to_json(&self) -> Value {
+        let hex = |bytes: &[u8]| {
+            format!(
+                "0x{}",
+                bytes
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>()
+            )
+        };
+        let (kind, value) = match self {
+            LavaSeed::String(s) => ("String", json!(s)),
+            LavaSeed::U8(n) => ("u8", json!(n)),
+            LavaSeed::I8(n) => ("i8", json!(n)),
+            LavaSeed::U16(n) => ("u16", json!(n)),
+            LavaSeed::I16(n) => ("i16", json!(n)),
+            LavaSeed::U32(n) => ("u32", json!(n)),
+            LavaSeed::I32(n) => ("i32", json!(n)),
+            LavaSeed::U64(n) => ("u64", json!(n)),
+            LavaSeed::I64(n) => ("i64", json!(n)),
+            // Strings, since JSON numbers lose precision past 2^53.
+            LavaSeed::U128(n) => ("u128", json!(n.to_string())),
+            LavaSeed::I128(n) => ("i128", json!(n.to_string())),
+            LavaSeed::USize(n) => ("usize", json!(n)),
+            LavaSeed::ISize(n) => ("isize", json!(n)),
+            LavaSeed::Bool(b) => ("bool", json!(b)),
+            LavaSeed::Bytes(bytes) => ("bytes", json!(hex(bytes))),
+            LavaSeed::Hash(bytes) => ("[u8;32]", json!(hex(bytes))),
+            LavaSeed::PublicKey(p) => ("Pubkey", json!(p)),
+            LavaSeed::Arg { test, arg } => {
+                return json!({ "kind": "arg", "test": test, "arg": arg })
+            }
+            LavaSeed::AccountField {
+                account,
+                field,
+                account_type,
+            } => {
+                let mut seed =
+                    json!({ "kind": "accountField", "account": account, "field": field });
+                if let Some(account_type) = account_type {
+                    seed["type"] = json!(account_type);
+                }
+                return seed;
+            }
+        };
+        json!({ "kind": kind, "value": value })
+    }
+
+    /// The seed as the bytes generators derive the PDA from, with `usize`
+    /// and `isize` taking `usize_width` bytes. Public keys, args and account
+    /// fields are left to [`LavaConfig::seed`], which knows what they refer
+    /// to.
+    pub(crate) fn lower(&self, usize_width: u8) -> Result<Seed, String> {
+        let int = |value: String, signed, bytes| {
+            Ok(Seed::Integer {
+                value,
+                signed,
+                bytes,
+            })
+        };
+        if matches!(self, LavaSeed::USize(_) | LavaSeed::ISize(_)) && ![4, 8].contains(&usize_width)
+        {
+            return Err(format!("usizeWidth must be 4 or 8, not {}", usize_width));
+        }
         match self {
-            LavaSeed::String(s) => format!("Buffer.from(\"{}\", \"utf-8\")", s),
-            LavaSeed::U8(n) => format!("new BN({}).toBuffer(\"le\", 1)", n),
-            LavaSeed::I8(n) => format!("new BN({}).toBuffer(\"le\", 1)", n),
-            LavaSeed::U16(n) => format!("new BN({}).toBuffer(\"le\", 2)", n),
-            LavaSeed::I16(n) => format!("new BN({}).toBuffer(\"le\", 2)", n),
-            LavaSeed::U32(n) => format!("new BN({}).toBuffer(\"le\", 4)", n),
-            LavaSeed::I32(n) => format!("new BN({}).toBuffer(\"le\", 4)", n),
-            LavaSeed::U64(n) => format!("new BN({}).toBuffer(\"le\", 8)", n),
-            LavaSeed::I64(n) => format!("new BN({}).toBuffer(\"le\", 8)", n),
-            // TODO: Handle encoding properly based upon size of the usize
-            LavaSeed::USize(n) => format!("new BN({}).toBuffer(\"le\", 8)", n),
-            LavaSeed::ISize(n) => format!("new BN({}).toBuffer(\"le\", 8)", n),
-            LavaSeed::PublicKey(n) => {
-                if pda_owner {
-                    format!("{}.publicKey.toBuffer()", n.to_case(Case::Camel))
-                } else {
-                    format!("{}.toBuffer()", n.to_case(Case::Camel))
+            LavaSeed::String(s) => Ok(Seed::String(s.clone())),
+            LavaSeed::U8(n) => int(n.to_string(), false, 1),
+            LavaSeed::I8(n) => int(n.to_string(), true, 1),
+            LavaSeed::U16(n) => int(n.to_string(), false, 2),
+            LavaSeed::I16(n) => int(n.to_string(), true, 2),
+            LavaSeed::U32(n) => int(n.to_string(), false, 4),
+            LavaSeed::I32(n) => int(n.to_string(), true, 4),
+            LavaSeed::U64(n) => int(n.to_string(), false, 8),
+            LavaSeed::I64(n) => int(n.to_string(), true, 8),
+            LavaSeed::U128(n) => int(n.to_string(), false, 16),
+            LavaSeed::I128(n) => int(n.to_string(), true, 16),
+            LavaSeed::USize(n) => {
+                let value = integer(&json!(n), false, usize_width as u32 * 8)?;
+                int(value, false, usize_width)
+            }
+            LavaSeed::ISize(n) => {
+                let value = integer(&json!(n), true, usize_width as u32 * 8)?;
+                int(value, true, usize_width)
+            }
+            LavaSeed::Bool(b) => Ok(Seed::Bytes(vec![*b as u8])),
+            LavaSeed::Bytes(bytes) => Ok(Seed::Bytes(bytes.clone())),
+            LavaSeed::Hash(bytes) => Ok(Seed::Bytes(bytes.to_vec())),
+            LavaSeed::PublicKey(p) => Err(format!("\"{}\" is an account, not a value", p)),
+            LavaSeed::Arg { test, arg } => Err(format!(
+                "\"{}\" of test \"{}\" is an arg, not a value",
+                arg, test
+            )),
+            LavaSeed::AccountField { account, field, .. } => Err(format!(
+                "\"{}\" of \"{}\" is an account field, not a value",
+                field, account
+            )),
+        }
+    }
+}
+
+/// The shared constant generators hold the arg `arg` of test `test` in.
+pub(crate) fn constant_name(test: &str, arg: &str) -> String {
+    format!("{}_{}", test.to_case(Case::Snake), arg.to_case(Case::Snake))
+}
+
+/// Whether a value of type `kind` has a byte encoding generators know how to
+/// seed a PDA with.
+fn seedable(kind: &InstructionType) -> bool {
+    match kind {
+        InstructionType::Array(inner, _) => matches!(**inner, InstructionType::U8),
+        InstructionType::Option(_)
+        | InstructionType::Vec(_)
+        | InstructionType::Tuple(_)
+        | InstructionType::Defined(_)
+        | InstructionType::HashSet(_)
+        | InstructionType::BTreeSet(_)
+        | InstructionType::HashMap(..)
+        | InstructionType::BTreeMap(..) => false,
+        _ => true,
+    }
+}
+
+/// The `pda.seeds` an IDL gives for an instruction account, translated for
+/// the test that maps the account.
+pub(crate) struct IdlSeeds {
+    pub(crate) test: String,
+    /// The instruction account, as named in the IDL.
+    pub(crate) account: String,
+    pub(crate) seeds: Result<Vec<LavaSeed>, String>,
+}
+
+/// What a seed contributes, when that is known before the tests run.
+#[derive(PartialEq, Eq)]
+enum Fixed {
+    Bytes(Vec<u8>),
+    Account(AccountRef),
+}
+
+/// The `bytes` wide little-endian encoding of the decimal integer `value`.
+fn le_bytes(value: &str, bytes: usize) -> Option<Vec<u8>> {
+    let n = if value.starts_with('-') {
+        value.parse::<i128>().ok()?
+    } else {
+        value.parse::<u128>().ok()? as i128
+    };
+    n.to_le_bytes().get(..bytes).map(|b| b.to_vec())
+}
+
+fn fixed(seed: &Seed) -> Option<Fixed> {
+    let bytes = match seed {
+        Seed::String(s) => s.as_bytes().to_vec(),
+        Seed::Integer { value, bytes, .. } => le_bytes(value, *bytes as usize)?,
+        Seed::Bytes(bytes) => bytes.clone(),
+        Seed::Account(account) => return Some(Fixed::Account(account.clone())),
+        Seed::Arg { value, .. } => match value {
+            ArgValue::Integer { value, bits, .. } => le_bytes(value, *bits as usize / 8)?,
+            ArgValue::Bool(b) => vec![*b as u8],
+            ArgValue::String(s) => s.as_bytes().to_vec(),
+            ArgValue::Bytes(bytes) => bytes.clone(),
+            ArgValue::Array { items, .. } => items
+                .iter()
+                .map(|i| match i {
+                    ArgValue::Integer { value, .. } => le_bytes(value, 1).map(|b| b[0]),
+                    _ => None,
+                })
+                .collect::<Option<Vec<u8>>>()?,
+            _ => return None,
+        },
+        // Only known once the account exists.
+        Seed::Field { .. } => return None,
+    };
+    Some(Fixed::Bytes(bytes))
+}
+
+impl LavaConfig {
+    /// Translates one entry of an IDL account's `pda.seeds` for test `t`:
+    /// accounts become whatever `t` maps them to and args whatever it passes.
+    fn idl_seed(&self, t: &LavaTest, args: &[String], seed: &Value) -> Result<LavaSeed, String> {
+        let path = seed["path"].as_str().unwrap_or_default();
+        match (seed["kind"].as_str(), seed["type"].as_str(), &seed["value"]) {
+            (Some("const"), _, value @ Value::Array(_)) => {
+                LavaSeed::try_from(json!({ "kind": "bytes", "value": value }))
+                    .map_err(|e| e.to_string())
+            }
+            (Some("const"), Some("string"), Value::String(s)) => Ok(LavaSeed::String(s.clone())),
+            (Some("const"), Some("publicKey"), Value::String(s)) => {
+                Ok(LavaSeed::PublicKey(s.clone()))
+            }
+            (Some("const"), Some(kind), value) => {
+                LavaSeed::try_from(json!({ "kind": kind, "value": value }))
+                    .map_err(|e| e.to_string())
+            }
+            (Some("account"), _, _) => {
+                let (account, field) = match path.split_once('.') {
+                    Some((account, field)) => (account, Some(field)),
+                    None => (path, None),
+                };
+                let mapped = t
+                    .accounts
+                    .as_object()
+                    .and_then(|m| {
+                        m.iter()
+                            .find(|(n, _)| account_key(n) == account_key(account))
+                    })
+                    .and_then(|(_, v)| v.as_str())
+                    .ok_or(format!(
+                        "seed account \"{}\" isn't mapped by test \"{}\"",
+                        account, t.name
+                    ))?;
+                match field {
+                    None => Ok(LavaSeed::PublicKey(mapped.to_string())),
+                    Some(field) if !field.contains('.') => Ok(LavaSeed::AccountField {
+                        account: mapped.to_string(),
+                        field: field.to_string(),
+                        account_type: seed["account"].as_str().map(|a| a.to_string()),
+                    }),
+                    Some(_) => Err(format!("nested field seed \"{}\" isn't supported", path)),
+                }
+            }
+            (Some("arg"), _, _) => args
+                .iter()
+                .find(|a| account_key(a) == account_key(path))
+                .map(|arg| LavaSeed::Arg {
+                    test: t.name.clone(),
+                    arg: arg.clone(),
+                })
+                .ok_or(format!(
+                    "arg seed \"{}\" isn't a plain instruction arg",
+                    path
+                )),
+            _ => Err(format!("unsupported IDL seed {}", seed)),
+        }
+    }
+
+    /// The IDL `pda.seeds` of every instruction account a test maps the PDA
+    /// `pda` to. Accounts derived from another program's ID are left out.
+    pub(crate) fn idl_seeds(&self, pda: &str) -> Vec<IdlSeeds> {
+        let mut found = vec![];
+        for t in &self.tests {
+            let Some(idl) = self.idl(&t.program_id) else {
+                continue;
+            };
+            let Some(i) = idl
+                .instructions
+                .iter()
+                .position(|i| i.name == t.instruction)
+            else {
+                continue;
+            };
+            let args = idl.instructions[i]
+                .args
+                .iter()
+                .map(|a| a.name.clone())
+                .collect::<Vec<String>>();
+            let raw = idl.json();
+            for account in raw["instructions"][i]["accounts"]
+                .as_array()
+                .into_iter()
+                .flatten()
+            {
+                let name = account["name"].as_str().unwrap_or_default();
+                let maps_pda = t
+                    .accounts
+                    .get(name)
+                    .and_then(|v| v.as_str())
+                    .is_some_and(|v| account_key(v) == account_key(pda));
+                let Some(seeds) = account["pda"]["seeds"].as_array() else {
+                    continue;
+                };
+                if !maps_pda || seeds.is_empty() || !account["pda"]["programId"].is_null() {
+                    continue;
                 }
+                found.push(IdlSeeds {
+                    test: t.name.clone(),
+                    account: name.to_string(),
+                    seeds: seeds.iter().map(|s| self.idl_seed(t, &args, s)).collect(),
+                });
             }
         }
+        found
+    }
+
+    /// The seeds `pda` is derived from: the ones written in the workspace,
+    /// or else those the IDL gives for an account a test maps it to. IDL
+    /// seeds that read the PDA's own data are skipped, and ones that read
+    /// no account data at all are preferred.
+    pub(crate) fn pda_seeds(&self, pda: &LavaPDA) -> Vec<LavaSeed> {
+        if !pda.seeds.is_empty() {
+            return pda.seeds.clone();
+        }
+        let reads = |seeds: &Vec<LavaSeed>, own: bool| {
+            seeds.iter().any(|s| match s {
+                LavaSeed::AccountField { account, .. } => {
+                    !own || account_key(account) == account_key(&pda.name)
+                }
+                _ => false,
+            })
+        };
+        let derived = self
+            .idl_seeds(&pda.name)
+            .into_iter()
+            .filter_map(|s| s.seeds.ok())
+            .filter(|seeds| !reads(seeds, true))
+            .collect::<Vec<_>>();
+        derived
+            .iter()
+            .find(|seeds| !reads(seeds, false))
+            .or(derived.first())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Where `seeds` and `idl` disagree on what a PDA is derived from, as
+    /// the index of the first differing seed, or `None` if they agree as
+    /// far as can be told before the tests run.
+    pub(crate) fn seed_mismatch(&self, seeds: &[LavaSeed], idl: &[LavaSeed]) -> Option<usize> {
+        if seeds.len() != idl.len() {
+            return Some(seeds.len().min(idl.len()));
+        }
+        seeds.iter().zip(idl).position(|(a, b)| {
+            let fixed = |s| self.seed(s).ok().as_ref().and_then(fixed);
+            matches!((fixed(a), fixed(b)), (Some(a), Some(b)) if a != b)
+        })
+    }
+
+    /// Whether `name` is a PDA seeded by a field of another account. Its
+    /// address can only be derived once that account exists, so generators
+    /// derive it in each test that uses it rather than up front.
+    pub(crate) fn is_deferred(&self, name: &str) -> bool {
+        self.pdas.values().any(|pda| {
+            account_key(&pda.name) == account_key(name)
+                && self
+                    .pda_seeds(pda)
+                    .iter()
+                    .any(|s| matches!(s, LavaSeed::AccountField { .. }))
+        })
+    }
+
+    /// Resolves `seed` against the workspace, the IDLs and the tests.
+    pub(crate) fn seed(&self, seed: &LavaSeed) -> Result<Seed, (LavaErrorKind, String)> {
+        let invalid = |e: String| (LavaErrorKind::InvalidSeed, e);
+        let deferred = |name: &str| {
+            if self.is_deferred(name) {
+                Err(invalid(format!(
+                    "\"{}\" is itself seeded by an account field, so it can't seed other accounts",
+                    name
+                )))
+            } else {
+                Ok(())
+            }
+        };
+        match seed {
+            LavaSeed::PublicKey(p) => {
+                deferred(p)?;
+                Ok(Seed::Account(self.resolve(p)))
+            }
+            LavaSeed::Arg { test, arg } => {
+                let t = self
+                    .tests
+                    .iter()
+                    .find(|t| &t.name == test)
+                    .ok_or(invalid(format!("there is no test \"{}\"", test)))?;
+                let idl = self.idl(&t.program_id).ok_or(invalid(format!(
+                    "test \"{}\" calls an unknown program",
+                    test
+                )))?;
+                let instruction = idl
+                    .instructions
+                    .iter()
+                    .find(|i| i.name == t.instruction)
+                    .ok_or(invalid(format!(
+                        "test \"{}\" calls an unknown instruction",
+                        test
+                    )))?;
+                let (i, kind) = instruction
+                    .args
+                    .iter()
+                    .enumerate()
+                    .find(|(_, a)| &a.name == arg)
+                    .map(|(i, a)| (i, &a.kind))
+                    .ok_or(invalid(format!(
+                        "\"{}\" has no arg \"{}\"",
+                        instruction.name, arg
+                    )))?;
+                if !seedable(kind) {
+                    return Err(invalid(format!("arg \"{}\" can't seed a PDA", arg)));
+                }
+                let value = t
+                    .args
+                    .get(i)
+                    .ok_or(invalid(format!(
+                        "test \"{}\" doesn't pass \"{}\"",
+                        test, arg
+                    )))
+                    .and_then(|v| {
+                        self.arg_value(idl, kind, v)
+                            .map_err(|e| invalid(format!("arg \"{}\": {}", arg, e)))
+                    })?;
+                match value {
+                    ArgValue::PublicKey(account) => {
+                        deferred(account.identifier())?;
+                        Ok(Seed::Account(account))
+                    }
+                    value => Ok(Seed::Arg {
+                        constant: constant_name(test, arg),
+                        kind: kind.clone(),
+                        value,
+                    }),
+                }
+            }
+            LavaSeed::AccountField {
+                account,
+                field,
+                account_type,
+            } => {
+                let Some(pda) = self
+                    .pdas
+                    .values()
+                    .find(|p| account_key(&p.name) == account_key(account))
+                else {
+                    let kind = if self.has_account(account) {
+                        LavaErrorKind::InvalidSeed
+                    } else {
+                        LavaErrorKind::UnknownAccount
+                    };
+                    return Err((
+                        kind,
+                        format!(
+                            "\"{}\" is not a declared PDA, so it holds no program account",
+                            account
+                        ),
+                    ));
+                };
+                deferred(&pda.name)?;
+                let idl = self.idl(&pda.program).ok_or(invalid(format!(
+                    "\"{}\" belongs to a program with no IDL",
+                    account
+                )))?;
+                let account_type = account_type
+                    .clone()
+                    .unwrap_or(pda.name.to_case(Case::Pascal));
+                let Ok(DefinedType::Struct(layout)) = defined_type(idl, &account_type) else {
+                    return Err(invalid(format!(
+                        "\"{}\" is not an account of \"{}\"",
+                        account_type, idl.name
+                    )));
+                };
+                let (field, kind) = layout
+                    .into_iter()
+                    .find(|(f, _)| account_key(f) == account_key(field))
+                    .ok_or(invalid(format!(
+                        "\"{}\" has no field \"{}\"",
+                        account_type, field
+                    )))?;
+                if !seedable(&kind) {
+                    return Err(invalid(format!("field \"{}\" can't seed a PDA", field)));
+                }
+                Ok(Seed::Field {
+                    account: self.resolve(&pda.name),
+                    program: idl.name.clone(),
+                    account_type,
+                    field,
+                    kind,
+                })
+            }
+            _ => seed.lower(self.usize_width).map_err(invalid),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::LavaSeed;
+    use crate::codegen::{AccountKind, AccountRef, ArgValue, Constant, Seed};
+    use crate::fixtures;
+    use crate::lava_config::LavaPDA;
+
+    fn seed(kind: &str, value: serde_json::Value) -> Result<LavaSeed, String> {
+        LavaSeed::try_from(json!({ "kind": kind, "value": value })).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn parses_wide_and_raw_seeds() {
+        let max = u128::MAX.to_string();
+        assert!(matches!(
+            seed("u128", json!(max)),
+            Ok(LavaSeed::U128(u128::MAX))
+        ));
+        assert!(matches!(seed("i128", json!(-1)), Ok(LavaSeed::I128(-1))));
+        assert!(matches!(
+            seed("bool", json!(true)),
+            Ok(LavaSeed::Bool(true))
+        ));
+        assert!(
+            matches!(seed("bytes", json!("0xdead")), Ok(LavaSeed::Bytes(b)) if b == [0xde, 0xad])
+        );
+        assert!(matches!(seed("bytes", json!("2g")), Ok(LavaSeed::Bytes(b)) if b == [0x61]));
+        assert!(
+            matches!(seed("[u8;32]", json!(vec![7; 32])), Ok(LavaSeed::Hash(h)) if h == [7; 32])
+        );
+        assert!(seed("[u8;32]", json!(vec![7; 31])).is_err());
+        assert!(seed("u8", json!(256)).is_err());
+
+        let hash = seed("[u8; 32]", json!(vec![1; 32])).unwrap();
+        assert_eq!(
+            LavaSeed::try_from(hash.to_json()).unwrap().to_json(),
+            hash.to_json()
+        );
+    }
+
+    #[test]
+    fn reads_legacy_seeds() {
+        let pda: LavaPDA = serde_json::from_value(json!({
+            "name": "Escrow",
+            "program": "anchor_escrow",
+            "seeds": [
+                { "String": "escrow" },
+                { "PublicKey": "11111111111111111111111111111111" },
+                { "U64": 1 },
+                { "USize": 2 }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            pda.seeds.iter().map(LavaSeed::to_json).collect::<Vec<_>>(),
+            vec![
+                json!({ "kind": "String", "value": "escrow" }),
+                json!({ "kind": "Pubkey", "value": "11111111111111111111111111111111" }),
+                json!({ "kind": "u64", "value": 1 }),
+                json!({ "kind": "usize", "value": 2 }),
+            ]
+        );
+        assert!(LavaSeed::try_from(json!({ "U64": -1 })).is_err());
+    }
+
+    #[test]
+    fn sizes_usize_to_the_target() {
+        let usize = seed("usize", json!(1u64 << 40)).unwrap();
+        assert!(matches!(
+            usize.lower(8),
+            Ok(Seed::Integer {
+                bytes: 8,
+                signed: false,
+                ..
+            })
+        ));
+        assert!(usize.lower(4).is_err());
+        assert!(matches!(
+            seed("isize", json!(-2)).unwrap().lower(4),
+            Ok(Seed::Integer { bytes: 4, signed: true, value }) if value == "-2"
+        ));
+    }
+
+    #[test]
+    fn plans_little_endian_seeds() {
+        let mut json = fixtures::dice();
+        json["usizeWidth"] = 4.into();
+        json["accounts"].as_array_mut().unwrap().push(json!({
+            "name": "bet",
+            "kind": "pda",
+            "program": "soldice_anchor",
+            "seeds": [
+                { "kind": "u128", "value": "340282366920938463463374607431768211455" },
+                { "kind": "i16", "value": -2 },
+                { "kind": "bool", "value": true },
+                { "kind": "bytes", "value": "0xbeef" },
+                { "kind": "usize", "value": 7 }
+            ]
+        }));
+        let plan = fixtures::config(&json).plan().unwrap();
+
+        let bet = plan
+            .accounts
+            .iter()
+            .find(|a| a.identifier == "bet")
+            .unwrap();
+        let AccountKind::Pda { program, seeds } = &bet.kind else {
+            panic!("expected a PDA");
+        };
+        assert_eq!(program, "soldice_anchor");
+        assert!(matches!(
+            &seeds[..],
+            [
+                Seed::Integer { value: max, signed: false, bytes: 16 },
+                Seed::Integer { value: minus_two, signed: true, bytes: 2 },
+                Seed::Bytes(bool),
+                Seed::Bytes(bytes),
+                Seed::Integer { value: seven, signed: false, bytes: 4 },
+            ] if *max == u128::MAX.to_string()
+                && minus_two == "-2"
+                && *bool == [1]
+                && *bytes == [0xbe, 0xef]
+                && seven == "7"
+        ));
+    }
+
+    #[test]
+    fn shares_seeds_with_tests() {
+        let mut json = fixtures::escrow();
+        let accounts = json["accounts"].as_array_mut().unwrap();
+        accounts[10]["seeds"][2] = json!({ "kind": "arg", "test": "Make", "arg": "seed" });
+        accounts.push(json!({
+            "name": "Receipt",
+            "kind": "pda",
+            "program": "anchor_escrow",
+            "seeds": [
+                { "kind": "String", "value": "receipt" },
+                { "kind": "accountField", "account": "Escrow", "field": "mintA" }
+            ]
+        }));
+        json["tests"][1]["assert"] = json!([{ "kind": "closed", "account": "Receipt" }]);
+        let plan = fixtures::config(&json).plan().unwrap();
+
+        // The seed arg is declared once, and both the PDA and Make use it.
+        assert!(matches!(
+            &plan.constants[..],
+            [Constant { identifier, program, value: ArgValue::Integer { value, bits: 64, .. } }]
+                if identifier == "make_seed" && program == "anchor_escrow" && value == "1"
+        ));
+        let pda = |id: &str| {
+            let account = plan.accounts.iter().find(|a| a.identifier == id).unwrap();
+            match &account.kind {
+                AccountKind::Pda { seeds, .. } => (account.is_deferred(), seeds.clone()),
+                _ => panic!("expected {} to be a PDA", id),
+            }
+        };
+        let (deferred, seeds) = pda("escrow");
+        assert!(!deferred);
+        assert!(matches!(&seeds[2], Seed::Arg { constant, .. } if constant == "make_seed"));
+        assert_eq!(plan.tests[0].args[0].constant.as_deref(), Some("make_seed"));
+
+        // The receipt can only be derived once the escrow exists.
+        let (deferred, seeds) = pda("receipt");
+        assert!(deferred);
+        assert!(matches!(
+            &seeds[1],
+            Seed::Field { account: AccountRef::Address(escrow), account_type, field, .. }
+                if escrow == "escrow" && account_type == "Escrow" && field == "mintA"
+        ));
+        assert!(plan.tests[1].uses("receipt"));
     }
 }
@@ -29,7 +29,7 @@ describe("Escrow", () => {
     const connection = provider.connection;
 
 
-const program = anchor.workspace.AnchorEscrow as Program<AnchorEscrow>;
+const anchor_escrow_program = anchor.workspace.AnchorEscrow as Program<AnchorEscrow>;
 
     const confirm = async (signature: string): Promise<string> => {
         const block = await connection.getLatestBlockhash();
@@ -52,25 +52,25 @@ const maker = Keypair.generate();
 const taker = Keypair.generate();
 const token_a = Keypair.generate();
 const token_b = Keypair.generate();
-const escrow = PublicKey.findProgramAddressSync([Buffer.from("escrow", "utf-8"), maker.publicKey.toBuffer(), new BN(1).toBuffer("le", 8)], program.programId)[0]
-const vault = getAssociatedTokenAddressSync(token_a.publicKey, escrow, true);
-const maker_ata_b = getAssociatedTokenAddressSync(token_b.publicKey, maker.publicKey);
+const escrow = PublicKey.findProgramAddressSync([Buffer.from("escrow", "utf-8"), maker.publicKey.toBuffer(), new BN(1).toBuffer("le", 8)], anchor_escrow_program.programId)[0]
+const maker_ata_a = getAssociatedTokenAddressSync(token_a.publicKey, maker.publicKey);
 const taker_ata_a = getAssociatedTokenAddressSync(token_a.publicKey, taker.publicKey);
+const vault = getAssociatedTokenAddressSync(token_a.publicKey, escrow, true);
 const taker_ata_b = getAssociatedTokenAddressSync(token_b.publicKey, taker.publicKey);
-const maker_ata_a = getAssociatedTokenAddressSync(token_a.publicKey, maker.publicKey);
+const maker_ata_b = getAssociatedTokenAddressSync(token_b.publicKey, maker.publicKey);
         const accountsPublicKeys = {maker: maker.publicKey,
 taker: taker.publicKey,
 token_a: token_a.publicKey,
 token_b: token_b.publicKey,
 escrow,
-vault,
-maker_ata_b,
+maker_ata_a,
 taker_ata_a,
+vault,
 taker_ata_b,
-maker_ata_a,
-            associatedTokenprogram: ASSOCIATED_TOKEN_PROGRAM_ID,
-            tokenProgram: TOKEN_PROGRAM_ID,
-            systemProgram: SystemProgram.programId
+maker_ata_b,
+token_program: TOKEN_PROGRAM_ID,
+associated_token_program: ASSOCIATED_TOKEN_PROGRAM_ID,
+system_program: SystemProgram.programId
     }
 
 it("setup", async() => {
@@ -99,6 +99,7 @@ it("setup", async() => {
         space: MINT_SIZE,
         programId: TOKEN_PROGRAM_ID,
       }),
+createInitializeMint2Instruction(token_a.publicKey, 6, provider.publicKey, null),
 SystemProgram.createAccount({
         fromPubkey: provider.publicKey,
         newAccountPubkey: token_b.publicKey,
@@ -106,30 +107,19 @@ SystemProgram.createAccount({
         space: MINT_SIZE,
         programId: TOKEN_PROGRAM_ID,
       }),
-createInitializeMint2Instruction(
-        token_b.publicKey,
-        6,
-        taker.publicKey,
-        null
-      ),
-createAssociatedTokenAccountIdempotentInstruction(provider.publicKey, taker_ata_b, taker.publicKey, token_b.publicKey),
-createMintToInstruction(token_b.publicKey, taker_ata_b, taker.publicKey, 1000000000),
-createInitializeMint2Instruction(
-        token_a.publicKey,
-        6,
-        maker.publicKey,
-        null
-      ),
+createInitializeMint2Instruction(token_b.publicKey, 6, provider.publicKey, null),
 createAssociatedTokenAccountIdempotentInstruction(provider.publicKey, maker_ata_a, maker.publicKey, token_a.publicKey),
-createMintToInstruction(token_a.publicKey, maker_ata_a, maker.publicKey, 1000000000) ].forEach(instruction => {
+createMintToInstruction(token_a.publicKey, maker_ata_a, provider.publicKey, 1000000000),
+createAssociatedTokenAccountIdempotentInstruction(provider.publicKey, taker_ata_b, taker.publicKey, token_b.publicKey),
+createMintToInstruction(token_b.publicKey, taker_ata_b, provider.publicKey, 1000000000) ].forEach(instruction => {
         tx.add(instruction);
     });
     await provider.sendAndConfirm(tx, [token_a, token_b, maker, taker]).then(log);
 })
 
 it("Make", async() => {
-            const accounts = {associatedTokenProgram: accountsPublicKeys["associated_token_program"], escrow: accountsPublicKeys["escrow"], maker: accountsPublicKeys["maker"], makerAtaA: accountsPublicKeys["maker_ata_a"], mintA: accountsPublicKeys["token_a"], mintB: accountsPublicKeys["token_b"], systemProgram: accountsPublicKeys["system_program"], tokenProgram: accountsPublicKeys["token_program"], vault: accountsPublicKeys["vault"]}
-            await program.methods
+            const accounts = {maker: accountsPublicKeys["maker"], escrow: accountsPublicKeys["escrow"], mintA: accountsPublicKeys["token_a"], mintB: accountsPublicKeys["token_b"], makerAtaA: accountsPublicKeys["maker_ata_a"], vault: accountsPublicKeys["vault"], tokenProgram: accountsPublicKeys["token_program"], associatedTokenProgram: accountsPublicKeys["associated_token_program"], systemProgram: accountsPublicKeys["system_program"]}
+            await anchor_escrow_program.methods
             .make(new BN(1), new BN(1000000), new BN(1000000))
             .accounts({ ...accounts })
 .signers([maker])
@@ -0,0 +1,520 @@
+//! Token program settings of mints and ATAs: which token program owns them,
+//! and the Token-2022 extensions a mint is created with.
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::check::account_key;
+use crate::codegen::{AccountRef, Extension};
+use crate::error::LavaErrorKind;
+use crate::lava_config::{parse_address, LavaConfig, LavaMint, LavaTest};
+
+/// The program that owns a mint and the token accounts holding it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TokenProgram {
+    #[default]
+    Token,
+    Token2022,
+}
+
+impl TokenProgram {
+    pub(crate) fn is_token(&self) -> bool {
+        *self == TokenProgram::Token
+    }
+
+    /// The builtin the program is known as.
+    pub fn builtin(&self) -> AccountRef {
+        AccountRef::Builtin(
+            match self {
+                TokenProgram::Token => "tokenProgram",
+                TokenProgram::Token2022 => "token2022Program",
+            }
+            .to_string(),
+        )
+    }
+}
+
+/// The state token accounts of a mint start in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountState {
+    Initialized,
+    Frozen,
+}
+
+/// A Token-2022 mint extension. Authorities name a wallet, PDA or address
+/// and default to the provider.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum LavaExtension {
+    /// Transfers withhold `feeBasisPoints` of the amount, up to `maxFee`.
+    TransferFee {
+        fee_basis_points: u16,
+        max_fee: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        authority: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        withdraw_authority: Option<String>,
+    },
+    /// Balances accrue interest at `rate` basis points a year.
+    InterestBearing {
+        rate: i16,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        authority: Option<String>,
+    },
+    NonTransferable,
+    PermanentDelegate {
+        delegate: String,
+    },
+    /// Points to the account holding the mint's metadata, the mint itself
+    /// unless `address` says otherwise.
+    MetadataPointer {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        authority: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        address: Option<String>,
+    },
+    /// Metadata stored in the mint, which needs a `metadataPointer` to it.
+    /// `name` and `symbol` default to the mint's.
+    TokenMetadata {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        symbol: Option<String>,
+        #[serde(default)]
+        uri: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        update_authority: Option<String>,
+        #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+        additional: IndexMap<String, String>,
+    },
+    /// Transfers call `program`, a program or address.
+    TransferHook {
+        program: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        authority: Option<String>,
+    },
+    DefaultAccountState {
+        state: AccountState,
+    },
+}
+
+impl LavaExtension {
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            LavaExtension::TransferFee { .. } => "transferFee",
+            LavaExtension::InterestBearing { .. } => "interestBearing",
+            LavaExtension::NonTransferable => "nonTransferable",
+            LavaExtension::PermanentDelegate { .. } => "permanentDelegate",
+            LavaExtension::MetadataPointer { .. } => "metadataPointer",
+            LavaExtension::TokenMetadata { .. } => "tokenMetadata",
+            LavaExtension::TransferHook { .. } => "transferHook",
+            LavaExtension::DefaultAccountState { .. } => "defaultAccountState",
+        }
+    }
+}
+
+/// Size of a mint, or of a token account, before any extension.
+const BASE_LEN: usize = 165;
+const MINT_LEN: usize = 82;
+/// A token account holding a multisig is this long, so accounts that would
+/// be are padded to tell them apart.
+const MULTISIG_LEN: usize = 355;
+
+/// The bytes `extension` takes in the mint, not counting its type and
+/// length, if they are allocated up front. Token metadata is instead
+/// written to the mint as it is initialized.
+fn extension_len(extension: &Extension) -> Option<usize> {
+    match extension {
+        Extension::TransferFee { .. } => Some(108),
+        Extension::InterestBearing { .. } => Some(52),
+        Extension::NonTransferable => Some(0),
+        Extension::PermanentDelegate { .. } => Some(32),
+        Extension::MetadataPointer { .. } | Extension::TransferHook { .. } => Some(64),
+        Extension::DefaultAccountState { .. } => Some(1),
+        Extension::TokenMetadata { .. } => None,
+    }
+}
+
+/// The size a mint with `extensions` is created with.
+pub fn mint_len(extensions: &[Extension]) -> usize {
+    let sizes = extensions
+        .iter()
+        .filter_map(extension_len)
+        .collect::<Vec<usize>>();
+    if sizes.is_empty() {
+        return MINT_LEN;
+    }
+    // The mint is padded to a token account's size and tagged with its
+    // account type before each extension's type, length and data.
+    let len = BASE_LEN + 1 + sizes.iter().map(|s| 4 + s).sum::<usize>();
+    if len == MULTISIG_LEN {
+        len + 2
+    } else {
+        len
+    }
+}
+
+/// The size of a mint with `extensions` once its token metadata, if any,
+/// is written, which is what the mint must hold rent for.
+pub fn funded_mint_len(extensions: &[Extension]) -> usize {
+    let metadata = extensions
+        .iter()
+        .map(|e| match e {
+            Extension::TokenMetadata {
+                name,
+                symbol,
+                uri,
+                additional,
+                ..
+            } => {
+                let additional = additional
+                    .iter()
+                    .map(|(k, v)| 4 + k.len() + 4 + v.len())
+                    .sum::<usize>();
+                // Type and length, update authority, mint, the three
+                // strings and the additional fields, each length prefixed.
+                4 + 32
+                    + 32
+                    + (4 + name.len())
+                    + (4 + symbol.len())
+                    + (4 + uri.len())
+                    + 4
+                    + additional
+            }
+            _ => 0,
+        })
+        .sum::<usize>();
+    mint_len(extensions) + metadata
+}
+
+impl LavaConfig {
+    /// The token program of the mints and ATAs `test` maps, the token
+    /// program if it maps none.
+    pub(crate) fn test_token_program(&self, test: &LavaTest) -> Result<TokenProgram, String> {
+        let mut programs = vec![];
+        for name in test
+            .accounts
+            .as_object()
+            .into_iter()
+            .flat_map(|a| a.values())
+            .filter_map(|v| v.as_str())
+        {
+            let is = |n: &String| account_key(n) == account_key(name);
+            let program = match self.mints.values().find(|m| is(&m.name)) {
+                Some(mint) => mint.token_program,
+                None => match self.atas.values().find(|a| is(&a.name)) {
+                    Some(ata) => self.token_program(ata),
+                    None => continue,
+                },
+            };
+            if !programs.contains(&program) {
+                programs.push(program);
+            }
+        }
+        match programs[..] {
+            [] => Ok(TokenProgram::Token),
+            [program] => Ok(program),
+            _ => Err(
+                "its mints and ATAs are under both the token program and Token-2022".to_string(),
+            ),
+        }
+    }
+
+    /// Resolves an authority of a mint or extension, the provider unless
+    /// one is named.
+    pub(crate) fn authority(&self, reference: Option<&String>) -> Result<AccountRef, String> {
+        let Some(reference) = reference else {
+            return Ok(AccountRef::Provider);
+        };
+        match self.resolve(reference) {
+            AccountRef::Literal(address) => parse_address(&address)
+                .map(AccountRef::Literal)
+                .map_err(|_| format!("\"{}\" is not a declared account or an address", reference)),
+            account => Ok(account),
+        }
+    }
+
+    /// Checks `extension` of `mint` and resolves the accounts it names.
+    pub(crate) fn extension(
+        &self,
+        mint: &LavaMint,
+        extension: &LavaExtension,
+    ) -> Result<Extension, (LavaErrorKind, String)> {
+        let invalid = |e: String| (LavaErrorKind::InvalidToken, e);
+        let unknown = |e: String| (LavaErrorKind::UnknownAccount, e);
+        let has = |kind: &str| mint.extensions.iter().any(|e| e.kind() == kind);
+
+        if mint.token_program.is_token() {
+            return Err(invalid(format!(
+                "{} is a Token-2022 extension, but the mint is under the token program; set \"tokenProgram\": \"token2022\"",
+                extension.kind()
+            )));
+        }
+        Ok(match extension {
+            LavaExtension::TransferFee {
+                fee_basis_points,
+                max_fee,
+                authority,
+                withdraw_authority,
+            } => {
+                if *fee_basis_points > 10_000 {
+                    return Err(invalid(format!(
+                        "a fee of {} basis points is more than the whole transfer",
+                        fee_basis_points
+                    )));
+                }
+                Extension::TransferFee {
+                    authority: self.authority(authority.as_ref()).map_err(unknown)?,
+                    withdraw_authority: self
+                        .authority(withdraw_authority.as_ref())
+                        .map_err(unknown)?,
+                    basis_points: *fee_basis_points,
+                    max_fee: *max_fee,
+                }
+            }
+            LavaExtension::InterestBearing { rate, authority } => Extension::InterestBearing {
+                authority: self.authority(authority.as_ref()).map_err(unknown)?,
+                rate: *rate,
+            },
+            LavaExtension::NonTransferable => {
+                if let Some(other) = ["transferFee", "transferHook"].iter().find(|k| has(k)) {
+                    return Err(invalid(format!(
+                        "nonTransferable can't be combined with {}",
+                        other
+                    )));
+                }
+                Extension::NonTransferable
+            }
+            LavaExtension::PermanentDelegate { delegate } => Extension::PermanentDelegate {
+                delegate: self.authority(Some(delegate)).map_err(unknown)?,
+            },
+            LavaExtension::MetadataPointer { authority, address } => {
+                let address = match address {
+                    Some(address) => self.authority(Some(address)).map_err(unknown)?,
+                    None => self.resolve(&mint.name),
+                };
+                if has("tokenMetadata") && address != self.resolve(&mint.name) {
+                    return Err(invalid(
+                        "tokenMetadata is stored in the mint, so the pointer must point to it"
+                            .to_string(),
+                    ));
+                }
+                Extension::MetadataPointer {
+                    authority: self.authority(authority.as_ref()).map_err(unknown)?,
+                    address,
+                }
+            }
+            LavaExtension::TokenMetadata {
+                name,
+                symbol,
+                uri,
+                update_authority,
+                additional,
+            } => {
+                if !has("metadataPointer") {
+                    return Err(invalid(
+                        "tokenMetadata needs a metadataPointer to the mint".to_string(),
+                    ));
+                }
+                // Initializing the metadata takes the mint authority's
+                // signature.
+                if !matches!(
+                    self.resolve(&mint.mint_authority),
+                    AccountRef::Keypair(_) | AccountRef::Provider
+                ) {
+                    return Err(invalid(format!(
+                        "tokenMetadata is initialized by the mint authority, but \"{}\" has no keypair",
+                        mint.mint_authority
+                    )));
+                }
+                // So does writing each additional field, by the update
+                // authority.
+                let updater = self.authority(update_authority.as_ref()).map_err(unknown)?;
+                if !additional.is_empty()
+                    && !matches!(updater, AccountRef::Keypair(_) | AccountRef::Provider)
+                {
+                    return Err(invalid(format!(
+                        "tokenMetadata fields are written by the update authority, but \"{}\" has no keypair",
+                        update_authority.as_deref().unwrap_or_default()
+                    )));
+                }
+                Extension::TokenMetadata {
+                    update_authority: updater,
+                    name: name.clone().unwrap_or(mint.name.clone()),
+                    symbol: symbol.clone().unwrap_or(mint.symbol.clone()),
+                    uri: uri.clone(),
+                    additional: additional.clone().into_iter().collect(),
+                }
+            }
+            LavaExtension::TransferHook { program, authority } => Extension::TransferHook {
+                authority: self.authority(authority.as_ref()).map_err(unknown)?,
+                program: self.authority(Some(program)).map_err(unknown)?,
+            },
+            LavaExtension::DefaultAccountState { state } => {
+                if *state == AccountState::Frozen && mint.freeze_authority.is_none() {
+                    return Err(invalid(
+                        "accounts can only start frozen if the mint has a freeze authority"
+                            .to_string(),
+                    ));
+                }
+                // Nothing can be minted to a frozen ATA.
+                let funded = self
+                    .atas
+                    .values()
+                    .find(|a| a.amount > 0 && account_key(&a.mint) == account_key(&mint.name));
+                if let (AccountState::Frozen, Some(ata)) = (state, funded) {
+                    return Err(invalid(format!(
+                        "accounts start frozen, so \"{}\" can't be funded; set its amount to 0",
+                        ata.name
+                    )));
+                }
+                Extension::DefaultAccountState {
+                    frozen: *state == AccountState::Frozen,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{funded_mint_len, mint_len, TokenProgram};
+    use crate::codegen::{AccountKind, AccountRef, Extension, SetupStep};
+    use crate::fixtures;
+
+    #[test]
+    fn sizes_mints_like_token_2022() {
+        assert_eq!(mint_len(&[]), 82);
+        assert_eq!(mint_len(&[Extension::NonTransferable]), 170);
+        let pointer = Extension::MetadataPointer {
+            authority: AccountRef::Provider,
+            address: AccountRef::Keypair("mint".to_string()),
+        };
+        let metadata = Extension::TokenMetadata {
+            update_authority: AccountRef::Provider,
+            name: "Lava".to_string(),
+            symbol: "LAVA".to_string(),
+            uri: "".to_string(),
+            additional: vec![],
+        };
+        assert_eq!(mint_len(&[pointer.clone(), metadata.clone()]), 234);
+        assert_eq!(funded_mint_len(&[pointer, metadata]), 234 + 92);
+    }
+
+    #[test]
+    fn creates_token_2022_mints() {
+        let mut json = fixtures::escrow_make();
+        json["accounts"][2]["tokenProgram"] = "token2022".into();
+        json["accounts"][2]["extensions"] = json!([
+            { "kind": "tokenMetadata", "uri": "https://lava.dev/a.json", "additional": { "color": "red" } },
+            { "kind": "metadataPointer" },
+            { "kind": "transferFee", "feeBasisPoints": 50, "maxFee": 5000 },
+        ]);
+        // Token B stays under the token program, so the token program
+        // can't be told from the mints.
+        json["tests"][0]["accounts"]["tokenProgram"] = "token2022Program".into();
+        let plan = fixtures::config(&json).plan().unwrap();
+
+        let Some(SetupStep::CreateMint {
+            token_program: TokenProgram::Token2022,
+            extensions,
+            ..
+        }) = plan
+            .setup
+            .iter()
+            .find(|s| matches!(s, SetupStep::CreateMint { mint, .. } if mint == "token_a"))
+        else {
+            panic!("expected token_a to be created under Token-2022");
+        };
+        // Extensions come first, whatever order they are listed in, and
+        // the metadata once the mint is initialized.
+        assert!(matches!(
+            &extensions[..],
+            [
+                Extension::TransferFee { basis_points: 50, max_fee: 5000, .. },
+                Extension::MetadataPointer { address: AccountRef::Keypair(pointer), .. },
+                Extension::TokenMetadata { uri, additional, .. },
+            ] if pointer == "token_a"
+                && uri == "https://lava.dev/a.json"
+                && *additional == [("color".to_string(), "red".to_string())]
+        ));
+        // Sized for the fee and pointer, funded for the metadata too.
+        assert_eq!(mint_len(extensions), 346);
+        assert_eq!(funded_mint_len(extensions), 478);
+
+        // ATAs and mint-tos follow the program of their mint.
+        let ata = |id: &str| {
+            plan.accounts
+                .iter()
+                .find_map(|a| match &a.kind {
+                    AccountKind::Ata { token_program, .. } if a.identifier == id => {
+                        Some(*token_program)
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+        assert_eq!(ata("vault"), TokenProgram::Token2022);
+        assert_eq!(ata("taker_ata_b"), TokenProgram::Token);
+        assert!(plan.setup.iter().any(|s| matches!(
+            s,
+            SetupStep::MintTo { ata, amount: 1000000000, token_program: TokenProgram::Token2022, .. }
+                if ata == "maker_ata_a"
+        )));
+    }
+
+    #[test]
+    fn honors_mint_authorities() {
+        let mut json = fixtures::escrow();
+        let accounts = json["accounts"].as_array_mut().unwrap();
+        accounts[2]["mint_authority"] = "Escrow".into();
+        accounts[2]["freeze_authority"] = "Taker".into();
+        accounts[3]["mint_authority"] = "Maker".into();
+        accounts[6]["amount"] = 5.into();
+        let plan = fixtures::config(&json).plan().unwrap();
+
+        // The escrow can't sign setup, so the provider mints Token A and
+        // hands it over once its ATAs are funded.
+        let escrow = AccountRef::Address("escrow".into());
+        assert_eq!(plan.mint_authority("token_a"), AccountRef::Provider);
+        assert_eq!(
+            plan.mint_authority("token_b"),
+            AccountRef::Keypair("maker".into())
+        );
+        assert!(plan.setup.iter().any(|s| matches!(
+            s,
+            SetupStep::CreateMint { mint, freeze_authority: Some(AccountRef::Keypair(f)), .. }
+                if mint == "token_a" && f == "taker"
+        )));
+        let steps: Vec<String> = plan
+            .setup
+            .iter()
+            .filter_map(|s| match s {
+                SetupStep::MintTo { ata, amount, .. } => {
+                    Some(format!("mint {} to {}", amount, ata))
+                }
+                SetupStep::SetMintAuthority {
+                    mint, authority, ..
+                } if *authority == escrow => Some(format!("hand {} to escrow", mint)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                "mint 1000000000 to maker_ata_a",
+                "mint 5 to taker_ata_a",
+                "mint 1000000000 to taker_ata_b",
+                "hand token_a to escrow",
+            ]
+        );
+    }
+}